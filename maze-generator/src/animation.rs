@@ -0,0 +1,280 @@
+// Animated generation.  Every edge drawn by generate() is recorded, and the
+// maze is replayed as it grows: each step opens the wall between two cells
+// and the cell it leads to.  Cells opened during the current frame are shown
+// in red, which makes the tips of the growing paths visible.  The animation
+// is written as an animated GIF, an animated SVG, or a numbered sequence of
+// PNG frames.
+
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+
+use crate::Maze;
+
+// Frames in the animation when the steps per frame are not given
+const DEFAULT_FRAMES: usize = 100;
+
+// Image size the blocks are scaled up to fit, in pixels
+const IMAGE_SIZE_PX: i32 = 640;
+
+// How long the finished maze is held at the end of a GIF, in seconds
+const HOLD_SECONDS: f64 = 2.0;
+
+// Palette: solid, open, opened this frame, start and finish areas
+const PALETTE: [u8; 12] = [
+    0x20, 0x20, 0x20,
+    0xff, 0xff, 0xff,
+    0xe0, 0x20, 0x20,
+    0xff, 0xf0, 0x80
+];
+const SOLID: u8 = 0;
+const OPEN: u8 = 1;
+const NEW: u8 = 2;
+const AREA: u8 = 3;
+
+pub enum AnimationFormat {
+    Gif,
+    Svg,
+    Frames
+}
+
+pub struct AnimationSettings {
+    pub format: AnimationFormat,
+    pub frames_per_second: f64,
+
+    // None spreads the steps over DEFAULT_FRAMES frames.
+    pub steps_per_frame: Option<usize>
+}
+
+impl AnimationSettings {
+    pub fn filename(&self) -> &'static str {
+        match self.format {
+            AnimationFormat::Gif => "maze.gif",
+            AnimationFormat::Svg => "maze-animation.svg",
+            AnimationFormat::Frames => "frames"
+        }
+    }
+}
+
+// The step at which each block of the wall block grid opens up.  Blocks that
+// are open before the first step (the start and finish areas) open at step
+// 0, and walls that are never removed are None.
+struct Timeline {
+    columns: i32,
+    rows: i32,
+    open_step: Vec<Option<usize>>,
+    steps: usize
+}
+
+impl Maze {
+    // Start recording the edges drawn by generate().
+    pub fn record_history(&mut self) {
+        self.history = Some(Vec::new());
+    }
+
+    // Write the animation of a maze generated with its history recorded.
+    // Returns the number of frames written.
+    pub fn to_animation(&self, settings: &AnimationSettings, filename: &str) -> usize {
+        let timeline = self.get_timeline();
+        let steps_per_frame = settings.steps_per_frame
+            .unwrap_or(timeline.steps.div_ceil(DEFAULT_FRAMES))
+            .max(1);
+        // Frame 0 is the maze before the first step, and the last frame is
+        // the finished maze with nothing shown as new.
+        let frames = timeline.steps.div_ceil(steps_per_frame) + 2;
+
+        match settings.format {
+            AnimationFormat::Gif => {
+                let out = BufWriter::new(File::create(filename).unwrap());
+                write_gif(out, &timeline, steps_per_frame, frames, settings.frames_per_second).unwrap();
+            },
+            AnimationFormat::Svg => {
+                let mut out = BufWriter::new(File::create(filename).unwrap());
+                write_svg(&mut out, &timeline, steps_per_frame, frames, settings.frames_per_second).unwrap();
+            },
+            AnimationFormat::Frames => {
+                fs::create_dir_all(filename).unwrap();
+                for frame in 0..frames {
+                    let path = format!("{}/frame_{:05}.png", filename, frame);
+                    let out = BufWriter::new(File::create(path).unwrap());
+                    write_png(out, &timeline, steps_per_frame, frame).unwrap();
+                }
+            }
+        }
+
+        frames
+    }
+
+    fn get_timeline(&self) -> Timeline {
+        let blocks = self.wall_blocks();
+        let mut timeline = Timeline {
+            columns: blocks.columns,
+            rows: blocks.rows,
+            open_step: vec![None; (blocks.columns * blocks.rows) as usize],
+            steps: 0
+        };
+
+        let history = self.history.as_ref().expect("generation history was not recorded");
+        for (step, (x, y, direction)) in history.iter().enumerate() {
+            let cell = self.get_cell(*x, *y);
+            let next = self.get_linked(&cell, direction).unwrap();
+
+            // The blocks between the cells, then the cell itself, which is
+            // already open if it is in the finish area.  Between the cells
+            // is one wall, or for a tunnel in a weave maze, two walls and
+            // the cell it passes under.
+            let (column, row) = (2 * *x as i32 + 1, 2 * *y as i32 + 1);
+            let (next_column, next_row) = (2 * next.x as i32 + 1, 2 * next.y as i32 + 1);
+            let (step_column, step_row) = ((next_column - column).signum(), (next_row - row).signum());
+            let (mut between_column, mut between_row) = (column + step_column, row + step_row);
+            while (between_column, between_row) != (next_column, next_row) {
+                if timeline.open_step[(between_row * timeline.columns + between_column) as usize].is_none() {
+                    timeline.set(between_column, between_row, step + 1);
+                }
+                between_column += step_column;
+                between_row += step_row;
+            }
+            if !next.finish_area() {
+                timeline.set(next_column, next_row, step + 1);
+            }
+        }
+        timeline.steps = history.len();
+
+        // Everything else that is open in the finished maze starts open.
+        for row in 0..blocks.rows {
+            for column in 0..blocks.columns {
+                let index = (row * blocks.columns + column) as usize;
+                if !blocks.is_solid(column, row) && timeline.open_step[index].is_none() {
+                    timeline.open_step[index] = Some(0);
+                }
+            }
+        }
+
+        timeline
+    }
+}
+
+impl Timeline {
+    fn set(&mut self, column: i32, row: i32, step: usize) {
+        self.open_step[(row * self.columns + column) as usize] = Some(step);
+    }
+
+    // Palette index of a block once the given number of steps have been
+    // taken, where the last steps_per_frame of them are new.
+    fn color(&self, column: i32, row: i32, taken: usize, steps_per_frame: usize) -> u8 {
+        match self.open_step[(row * self.columns + column) as usize] {
+            Some(0) => AREA,
+            Some(step) if step > taken => SOLID,
+            Some(step) if step + steps_per_frame > taken => NEW,
+            Some(_) => OPEN,
+            None => SOLID
+        }
+    }
+
+    fn scale(&self) -> i32 {
+        (IMAGE_SIZE_PX / self.columns.max(self.rows)).max(1)
+    }
+
+    // One palette index per pixel, top row first.
+    fn pixels(&self, frame: usize, steps_per_frame: usize) -> Vec<u8> {
+        let scale = self.scale();
+        let width = (self.columns * scale) as usize;
+        let taken = frame * steps_per_frame;
+        let mut pixels: Vec<u8> = vec![SOLID; width * (self.rows * scale) as usize];
+
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let color = self.color(column, row, taken, steps_per_frame);
+
+                let top = ((self.rows - 1 - row) * scale) as usize;
+                let left = (column * scale) as usize;
+                for y in top..top + scale as usize {
+                    for pixel in pixels[y * width + left..y * width + left + scale as usize].iter_mut() {
+                        *pixel = color;
+                    }
+                }
+            }
+        }
+
+        pixels
+    }
+}
+
+fn write_gif<W: Write>(out: W, timeline: &Timeline, steps_per_frame: usize, frames: usize, frames_per_second: f64) -> Result<(), gif::EncodingError> {
+    let scale = timeline.scale();
+    let (width, height) = ((timeline.columns * scale) as u16, (timeline.rows * scale) as u16);
+    let mut encoder = gif::Encoder::new(out, width, height, &PALETTE)?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+
+    // GIF delays are in hundredths of a second.
+    let delay = (100.0 / frames_per_second).round().max(1.0) as u16;
+    for frame in 0..frames {
+        let pixels = timeline.pixels(frame, steps_per_frame);
+        encoder.write_frame(&gif::Frame {
+            width,
+            height,
+            delay: if frame + 1 == frames { (HOLD_SECONDS * 100.0) as u16 } else { delay },
+            buffer: Cow::Borrowed(&pixels),
+            ..gif::Frame::default()
+        })?;
+    }
+
+    Ok(())
+}
+
+fn write_png<W: Write>(out: W, timeline: &Timeline, steps_per_frame: usize, frame: usize) -> Result<(), png::EncodingError> {
+    let scale = timeline.scale();
+    let mut encoder = png::Encoder::new(out, (timeline.columns * scale) as u32, (timeline.rows * scale) as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(PALETTE.to_vec());
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&timeline.pixels(frame, steps_per_frame))
+}
+
+// Blocks opened during the same frame are grouped, and each group is hidden
+// until its frame, shown in red for one frame, then turns white.
+fn write_svg<W: Write>(out: &mut W, timeline: &Timeline, steps_per_frame: usize, frames: usize, frames_per_second: f64) -> std::io::Result<()> {
+    let color = |index: u8| {
+        let rgb = &PALETTE[3 * index as usize..3 * index as usize + 3];
+        format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+    };
+
+    let mut groups: Vec<Vec<(i32, i32)>> = vec![Vec::new(); frames];
+    for row in 0..timeline.rows {
+        for column in 0..timeline.columns {
+            if let Some(step) = timeline.open_step[(row * timeline.columns + column) as usize] {
+                groups[step.div_ceil(steps_per_frame)].push((column, timeline.rows - 1 - row));
+            }
+        }
+    }
+
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" shape-rendering=\"crispEdges\">",
+        timeline.columns * timeline.scale(), timeline.rows * timeline.scale(), timeline.columns, timeline.rows)?;
+    writeln!(out, "  <rect width=\"{}\" height=\"{}\" fill=\"{}\"/>", timeline.columns, timeline.rows, color(SOLID))?;
+
+    for (frame, blocks) in groups.iter().enumerate() {
+        if blocks.is_empty() {
+            continue;
+        }
+
+        if frame == 0 {
+            writeln!(out, "  <g fill=\"{}\">", color(AREA))?;
+        } else {
+            let begin = frame as f64 / frames_per_second;
+            writeln!(out, "  <g fill=\"{}\" visibility=\"hidden\">", color(NEW))?;
+            writeln!(out, "    <set attributeName=\"visibility\" to=\"visible\" begin=\"{:.3}s\" fill=\"freeze\"/>", begin)?;
+            writeln!(out, "    <set attributeName=\"fill\" to=\"{}\" begin=\"{:.3}s\" fill=\"freeze\"/>", color(OPEN), begin + 1.0 / frames_per_second)?;
+        }
+
+        for (x, y) in blocks {
+            writeln!(out, "    <rect x=\"{}\" y=\"{}\" width=\"1\" height=\"1\"/>", x, y)?;
+        }
+        writeln!(out, "  </g>")?;
+    }
+    writeln!(out, "</svg>")?;
+
+    Ok(())
+}
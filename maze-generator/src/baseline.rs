@@ -0,0 +1,222 @@
+// The generator as it was before cells were stored in flat vectors, kept for
+// the benchmark to compare against (see benchmark.rs).  Built only with the
+// 'baseline' feature:
+//
+//     cargo run --release --features baseline -- benchmark
+//
+// Cells are a column-major Vec<Vec<Cell>> of structs with a bool for each
+// edge and flag.  Every tip at a dead end walks back one cell per pass, often
+// all the way to the start, and is then removed from the middle of the list,
+// so generation is quadratic in the number of cells.  Only generation is
+// kept; the mazes are timed and thrown away.
+
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+
+use crate::Direction;
+
+#[derive(Copy, Clone)]
+struct Cell {
+    cell_type: u8,
+    x: u32,
+    y: u32,
+    edges: [bool; 4],
+    visited: bool,
+    start_area: bool,
+    finish_area: bool
+}
+
+impl Cell {
+    fn has_edge(&self, direction: &Direction) -> bool {
+        self.edges[edge_index(direction)]
+    }
+}
+
+fn edge_index(direction: &Direction) -> usize {
+    match direction {
+        Direction::North => 0,
+        Direction::East => 1,
+        Direction::South => 2,
+        Direction::West => 3
+    }
+}
+
+// The weights of the original meander strategies, inside and outside the
+// circle in the middle of the maze
+struct MeanderStrategy {
+    weight_north_south: u32,
+    weight_east_west: u32,
+    weight_forward: u32,
+    weight_turn_left: u32,
+    weight_turn_right: u32,
+    weight_same_cell_type: u32
+}
+
+const STRATEGIES: [MeanderStrategy; 2] = [
+    MeanderStrategy { weight_north_south: 1, weight_east_west: 100, weight_forward: 1,
+        weight_turn_left: 1, weight_turn_right: 1, weight_same_cell_type: 1 },
+    MeanderStrategy { weight_north_south: 1, weight_east_west: 1, weight_forward: 1,
+        weight_turn_left: 1, weight_turn_right: 1, weight_same_cell_type: 1 }
+];
+
+pub struct BaselineMaze {
+    cells: Vec<Vec<Cell>>,
+    x_size: u32,
+    y_size: u32,
+    goal_reached: bool,
+    start_x: u32,
+    start_y: u32,
+    rng: StdRng
+}
+
+impl BaselineMaze {
+    pub fn new(x_size: u32, y_size: u32, start_finish_size: u32) -> BaselineMaze {
+        let cells = (0..x_size)
+            .map(|x| (0..y_size)
+                .map(|y| {
+                    let dx = (x as f64 - x_size as f64 / 2.0) / x_size as f64;
+                    let dy = (y as f64 - y_size as f64 / 2.0) / x_size as f64;
+                    Cell {
+                        cell_type: if dx * dx + dy * dy < 0.15 { 0 } else { 1 },
+                        x,
+                        y,
+                        edges: [false; 4],
+                        visited: false,
+                        start_area: x < start_finish_size && y < start_finish_size,
+                        finish_area: x >= x_size - start_finish_size && y >= y_size - start_finish_size
+                    }
+                })
+                .collect())
+            .collect();
+
+        BaselineMaze {
+            cells,
+            x_size,
+            y_size,
+            goal_reached: false,
+            start_x: 0,
+            start_y: start_finish_size - 1,
+            rng: StdRng::from_entropy()
+        }
+    }
+
+    pub fn generate(&mut self) {
+        let mut paths: Vec<Cell> = vec![self.get_cell(self.start_x, self.start_y)];
+
+        while !paths.is_empty() {
+            // Loop through the paths and advance, or back-track, each one.
+            let mut index = 0;
+            while index < paths.len() {
+                let path = paths[index];
+                match self.meander(&path) {
+                    Some(direction) => {
+                        if self.rng.gen::<f64>() < 0.05 {
+                            paths.push(path);
+                        }
+
+                        let next = self.get_adjacent(&path, &direction).unwrap();
+                        self.cells[path.x as usize][path.y as usize].edges[edge_index(&direction)] = true;
+                        self.cells[next.x as usize][next.y as usize].visited = true;
+                        if next.finish_area {
+                            self.goal_reached = true;
+                        } else {
+                            paths[index] = self.get_cell(next.x, next.y);
+                        }
+                        index += 1;
+                    },
+                    None => {
+                        if path.x == self.start_x && path.y == self.start_y {
+                            paths.remove(index);
+                        } else {
+                            paths[index] = self.get_previous_cell(&path);
+                            index += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // A weighted random choice of the directions that lead to unvisited
+    // cells, or None at a dead end.
+    fn meander(&mut self, current: &Cell) -> Option<Direction> {
+        let strategy = &STRATEGIES[current.cell_type as usize];
+        let previous = self.get_previous_direction(current).unwrap_or(Direction::North);
+
+        let mut directions: Vec<Direction> = Vec::new();
+        let mut weights: Vec<u32> = Vec::new();
+        let mut direction = previous;
+        for _ in 0..4 {
+            direction = direction.right();
+            if !self.is_valid(current, &direction) {
+                continue;
+            }
+
+            let next = self.get_adjacent(current, &direction).unwrap();
+            let mut weight = match direction {
+                Direction::North | Direction::South => strategy.weight_north_south,
+                _ => strategy.weight_east_west
+            };
+            if current.cell_type == next.cell_type {
+                weight += strategy.weight_same_cell_type;
+            }
+            if direction == previous {
+                weight += strategy.weight_forward;
+            }
+            if direction == previous.left() {
+                weight += strategy.weight_turn_left;
+            }
+            if direction == previous.right() {
+                weight += strategy.weight_turn_right;
+            }
+            directions.push(direction);
+            weights.push(weight);
+        }
+
+        if directions.is_empty() {
+            return None;
+        }
+        let choice = WeightedIndex::new(&weights).unwrap();
+        Some(directions[choice.sample(&mut self.rng)])
+    }
+
+    // The direction of the edge that arrives at a visited cell.
+    fn get_previous_direction(&self, cell: &Cell) -> Option<Direction> {
+        let mut direction = Direction::North;
+        for _ in 0..4 {
+            if let Some(adjacent) = self.get_adjacent(cell, &direction) {
+                if adjacent.has_edge(&direction.opposite()) {
+                    return Some(direction.opposite());
+                }
+            }
+            direction = direction.right();
+        }
+        None
+    }
+
+    fn get_previous_cell(&self, cell: &Cell) -> Cell {
+        let backward = self.get_previous_direction(cell).unwrap().opposite();
+        self.get_adjacent(cell, &backward).unwrap()
+    }
+
+    fn get_adjacent(&self, cell: &Cell, direction: &Direction) -> Option<Cell> {
+        match direction {
+            Direction::North if cell.y < self.y_size - 1 => Some(self.get_cell(cell.x, cell.y + 1)),
+            Direction::South if cell.y > 0 => Some(self.get_cell(cell.x, cell.y - 1)),
+            Direction::East if cell.x < self.x_size - 1 => Some(self.get_cell(cell.x + 1, cell.y)),
+            Direction::West if cell.x > 0 => Some(self.get_cell(cell.x - 1, cell.y)),
+            _ => None
+        }
+    }
+
+    fn get_cell(&self, x: u32, y: u32) -> Cell {
+        self.cells[x as usize][y as usize]
+    }
+
+    fn is_valid(&self, current: &Cell, direction: &Direction) -> bool {
+        match self.get_adjacent(current, direction) {
+            None => false,
+            Some(next) => !(next.visited || next.start_area || next.finish_area && self.goal_reached)
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// Benchmark for maze generation and PDF rendering.  Run with
+//
+//     cargo run --release -- benchmark
+//
+// Each size is a square maze of micro (1/16 inch) cells, generated and then
+// rendered to a PDF in the system temporary directory.
+//
+// With the 'baseline' feature, the generator from before cells were stored in
+// flat vectors (see baseline.rs) is timed on the same sizes, up to
+// BASELINE_LIMIT, and the speedup over it is printed:
+//
+//     cargo run --release --features baseline -- benchmark
+
+use std::time::Instant;
+
+use crate::{Caption, Document, Maze, Theme};
+
+// Square maze sizes to generate, in cells per side.
+const GENERATE_SIZES: [u32; 5] = [250, 500, 1000, 2000, 5000];
+
+// The baseline generator is quadratic, taking about 40 seconds for a 2000
+// maze, so it is only timed up to this size.
+#[cfg(feature = "baseline")]
+const BASELINE_LIMIT: u32 = 1000;
+
+pub fn run() {
+    let filename = std::env::temp_dir().join("maze-benchmark.pdf");
+    let filename = filename.to_str().unwrap();
+
+    println!("{:>6} {:>12} {:>14} {:>16} {:>12} {:>10} {:>12} {:>8}",
+        "size", "cells", "generate ms", "cells per ms", "render ms", "pdf KB", "baseline ms", "speedup");
+
+    for size in GENERATE_SIZES.iter() {
+        let cells = *size as u64 * *size as u64;
+
+        let mut maze: Maze = Maze::new(*size, *size, 3);
+        let start = Instant::now();
+        maze.generate();
+        let generate_ms = start.elapsed().as_millis();
+        let cells_per_ms = cells as f64 / (generate_ms as f64).max(1.0);
+
+        let doc = benchmark_document(*size);
+        let start = Instant::now();
+        maze.to_pdf(&doc, true, filename);
+        let render_ms = start.elapsed().as_millis();
+        let pdf_kb = std::fs::metadata(filename).unwrap().len() / 1024;
+
+        let (baseline_ms, speedup) = match baseline_ms(*size) {
+            Some(baseline_ms) => (baseline_ms.to_string(), format!("{:.1}x", baseline_ms as f64 / (generate_ms as f64).max(1.0))),
+            None => ("-".to_string(), "-".to_string())
+        };
+
+        println!("{:>6} {:>12} {:>14} {:>16.0} {:>12} {:>10} {:>12} {:>8}",
+            size, cells, generate_ms, cells_per_ms, render_ms, pdf_kb, baseline_ms, speedup);
+    }
+
+    let _ = std::fs::remove_file(filename);
+}
+
+// Time the baseline generator on a square maze of the given size, if it is
+// built and the size is small enough.
+#[cfg(feature = "baseline")]
+fn baseline_ms(size: u32) -> Option<u128> {
+    if size > BASELINE_LIMIT {
+        return None;
+    }
+
+    let mut maze = crate::baseline::BaselineMaze::new(size, size, 3);
+    let start = Instant::now();
+    maze.generate();
+    Some(start.elapsed().as_millis())
+}
+
+#[cfg(not(feature = "baseline"))]
+fn baseline_ms(_size: u32) -> Option<u128> {
+    None
+}
+
+// A page just large enough to hold a square maze of micro cells.
+fn benchmark_document(size: u32) -> Document {
+    let margin_in = 1.0;
+    let page_in = size as f64 / 16.0 + 2.0 * margin_in;
+
+    Document {
+        page_height_in: page_in,
+        page_width_in: page_in,
+        line_width_pt: 0.0,
+        margin_in,
+        theme: Theme::new(),
+        blocks: None,
+        caption: Caption::new()
+    }
+}
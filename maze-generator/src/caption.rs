@@ -0,0 +1,174 @@
+// Captions and document information for PDFs.  A single page maze can have a
+// caption: its title in bold below the maze, with its difficulty rating, seed
+// and size on the same line to the right, and Start and Finish printed just
+// outside the corners with the home and the star.  The rating is the level
+// of difficulty whose ranges the maze's measures are closest to (see
+// difficulty.rs), whether or not one was asked for.
+//
+// Every maze PDF also records the settings it was generated with in its
+// document information, as "name=value" pairs in the keywords, where PDF
+// viewers show them and the maze can be regenerated from (see settings.rs).
+// The same keywords go in the XMP metadata that printpdf writes, so that the
+// two agree.
+
+use printpdf::*;
+use std::fs::File;
+use std::io::{self, BufWriter};
+
+use crate::content::StreamedContent;
+use crate::difficulty;
+use crate::topology::{DIGIT_WIDTH, LABEL_GAP_PT};
+use crate::update::{self, Update};
+use crate::{transform_x, transform_y, Document, Maze};
+
+const POINTS_PER_INCH: f64 = 72.0;
+
+// Font sizes of the title and of the rest of the caption, in points
+const TITLE_SIZE: f64 = 11.0;
+const TEXT_SIZE: f64 = 8.0;
+
+// The element of printpdf's XMP metadata that the keywords are written before
+const XMP_KEYWORDS_BEFORE: &str = "<pdf:Trapped>";
+
+#[derive(Clone)]
+pub struct Caption {
+    // The title printed below the maze, or None for no printed caption
+    pub title: Option<String>,
+
+    // The settings the maze was generated with, as names and values
+    pub settings: Vec<(&'static str, String)>
+}
+
+impl Caption {
+    pub fn new() -> Caption {
+        Caption {
+            title: None,
+            settings: Vec::new()
+        }
+    }
+
+    // The title of the PDF document.
+    pub fn document_title(&self) -> &str {
+        self.title.as_deref().unwrap_or("Maze")
+    }
+
+    // The settings as they are written to the keywords.
+    pub fn keywords(&self) -> String {
+        self.settings.iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<String>>()
+            .join("; ")
+    }
+}
+
+impl Maze {
+    // Print the caption, if the document has one, and the Start and Finish
+    // labels.
+    pub fn draw_caption(&self, layer: &PdfLayerReference, font: &IndirectFontRef, bold: &IndirectFontRef, doc: &Document) {
+        let title = match &doc.caption.title {
+            Some(title) => title,
+            None => return
+        };
+
+        let text = |text: &str, size: f64, x: f64, y: f64, font: &IndirectFontRef| {
+            layer.use_text(text, size, Mm::from(Pt(x)), Mm::from(Pt(y)), font);
+        };
+
+        // Walls may be drawn in white on a background, but the caption is
+        // outside the maze.
+        layer.save_graphics_state();
+        layer.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+
+        let baseline = doc.margin_in * 0.4 * POINTS_PER_INCH;
+        let right = (doc.page_width_in - doc.margin_in) * POINTS_PER_INCH;
+        let details = format!("Difficulty: {}    Seed: {}    {} x {} cells",
+            difficulty::rate(self.measure_difficulty()), self.seed, self.x_size, self.y_size);
+        text(title, TITLE_SIZE, doc.margin_in * POINTS_PER_INCH, baseline, bold);
+        text(&details, TEXT_SIZE, right - text_width(&details) * TEXT_SIZE, baseline, font);
+
+        // A race starts from two homes, drawn in the players' colors instead.
+        if self.race.is_none() {
+            let (x_size, y_size) = (self.x_size as i32, self.y_size as i32);
+            let area = self.start_finish_size as f64;
+            let offset = doc.line_width_pt / 2.0;
+            let centered = |label: &str, x: f64| {
+                (transform_x(x, x_size, doc).0 + transform_x(x + area, x_size, doc).0) / 2.0 + offset
+                    - text_width(label) * TEXT_SIZE / 2.0
+            };
+
+            text("Start", TEXT_SIZE, centered("Start", 0.0),
+                transform_y(0.0, y_size, doc).0 - LABEL_GAP_PT - TEXT_SIZE * 0.75, bold);
+            text("Finish", TEXT_SIZE, centered("Finish", self.x_size as f64 - area),
+                transform_y(self.y_size as f64, y_size, doc).0 + doc.line_width_pt + LABEL_GAP_PT, bold);
+        }
+
+        layer.restore_graphics_state();
+    }
+}
+
+// Rough width of text in Helvetica, as a fraction of the font size.
+fn text_width(text: &str) -> f64 {
+    text.chars()
+        .map(|c| match c {
+            '0'..='9' => DIGIT_WIDTH,
+            ' ' | '.' | ',' | ':' | 'i' | 'j' | 'l' | 'f' | 't' | 'I' => 0.28,
+            'm' | 'w' | 'M' | 'W' => 0.85,
+            'A'..='Z' => 0.68,
+            _ => 0.53
+        })
+        .sum()
+}
+
+// Save a PDF, with its streamed content spliced into its pages and the
+// settings of the document's caption in its keywords.
+pub fn save_pdf(pdf: PdfDocumentReference, doc: &Document, content: StreamedContent, filename: &str) {
+    pdf.save(&mut BufWriter::new(File::create(filename).unwrap())).unwrap();
+
+    let finished = Update::open(filename).and_then(|mut update| {
+        content.add_to(&mut update)?;
+        if !doc.caption.settings.is_empty() {
+            add_keywords(&mut update, &doc.caption.keywords())?;
+        }
+        update.write()
+    });
+    if let Err(error) = finished {
+        println!("Warning: could not finish {}: {}", filename, error);
+    }
+}
+
+// printpdf only writes the title into the document information and the XMP
+// metadata, so the keywords are added to copies of both in the update.
+fn add_keywords(update: &mut Update, keywords: &str) -> io::Result<()> {
+    let root = update.trailer_reference("/Root")?;
+    let info = update.trailer_reference("/Info")?;
+    let catalog = update.object(root)?;
+    let metadata = update::key_number(&catalog, b"/Metadata").ok_or_else(|| update::invalid("no XMP metadata"))?;
+
+    let information = update::dictionary(&update.object(info)?).ok_or_else(|| update::invalid("no document information"))?;
+    let mut information_object = information[..information.len() - 2].to_vec();
+    information_object.extend_from_slice(format!("/Keywords({})>>", escape_pdf(keywords)).as_bytes());
+
+    let xmp = with_xmp_keywords(&update::stream_content(&update.object(metadata)?)?, keywords)?;
+    update.replace(info, information_object);
+    update.replace(metadata, update::stream_object("/Type/Metadata/Subtype/XML", &xmp));
+    Ok(())
+}
+
+// The XMP metadata with a keywords element added before the marker.
+fn with_xmp_keywords(xmp: &[u8], keywords: &str) -> io::Result<Vec<u8>> {
+    let at = update::find(xmp, XMP_KEYWORDS_BEFORE.as_bytes())
+        .ok_or_else(|| update::invalid("XMP metadata without a place for the keywords"))?;
+
+    let mut patched = xmp[..at].to_vec();
+    patched.extend_from_slice(format!("<pdf:Keywords>{}</pdf:Keywords>\n         ", escape_xml(keywords)).as_bytes());
+    patched.extend_from_slice(&xmp[at..]);
+    Ok(patched)
+}
+
+fn escape_pdf(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('(', "\\(").replace(')', "\\)").replace('\r', "\\r")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,240 @@
+// Checkpoints.  Coins are scattered at random over the maze, to be collected
+// in any order on the way from the start to the finish.  The shortest tour is
+// found from the distance between every pair of stops, by solving the
+// travelling salesman problem over them exactly with the Held-Karp dynamic
+// program, which is quick for the handful of checkpoints allowed.  The coins
+// are drawn with the other icons in the maze, and the tour in the solution.
+
+use printpdf::*;
+use printpdf::utils::calculate_points_for_circle;
+use rand::prelude::*;
+use std::collections::VecDeque;
+
+use crate::{transform_x, transform_y, Document, Maze};
+
+// Radius of a coin, as a fraction of the cell size
+const COIN_RADIUS: f64 = 0.3;
+
+pub struct Checkpoints {
+    cells: Vec<(u32, u32)>,
+
+    // The shortest route from the start cell through every checkpoint and
+    // into the finish area
+    tour: Vec<(u32, u32)>
+}
+
+// The result of a breadth first search from one cell: the number of moves to
+// every cell, and the cell each one is reached from.
+pub struct Search {
+    pub distances: Vec<u32>,
+    previous: Vec<usize>
+}
+
+impl Maze {
+    // Scatter the given number of checkpoints outside the start and finish
+    // areas, and find the shortest tour.  Returns the number of moves in the
+    // tour.
+    pub fn add_checkpoints(&mut self, count: usize) -> usize {
+        let candidates: Vec<usize> = (0..self.flags.len())
+            .filter(|index| {
+                let cell = self.get_cell_at(*index);
+                !cell.start_area() && !cell.finish_area()
+            })
+            .collect();
+        let cells: Vec<(u32, u32)> = candidates.choose_multiple(&mut self.rng, count)
+            .map(|index| {
+                let cell = self.get_cell_at(*index);
+                (cell.x, cell.y)
+            })
+            .collect();
+
+        let tour = self.get_tour(&cells);
+        let moves = tour.len() - 1;
+        self.checkpoints = Some(Checkpoints { cells, tour });
+        moves
+    }
+
+    // The tour, if checkpoints have been placed.
+    pub fn tour_route(&self) -> Option<Vec<(u32, u32)>> {
+        self.checkpoints.as_ref().map(|checkpoints| checkpoints.tour.clone())
+    }
+
+    // The shortest route from the start through every given cell to the
+    // cell where the solution enters the finish area.
+    fn get_tour(&self, cells: &[(u32, u32)]) -> Vec<(u32, u32)> {
+        // Stop 0 is the start, stops 1 to n the checkpoints, and stop n + 1
+        // the finish.
+        let mut stops: Vec<(u32, u32)> = vec![(self.start_x, self.start_y)];
+        stops.extend_from_slice(cells);
+        stops.push((self.finish_x, self.finish_y));
+        let searches: Vec<Search> = stops.iter().map(|stop| self.search_from(*stop)).collect();
+        let distance = |from: usize, to: usize| {
+            let (x, y) = stops[to];
+            searches[from].distances[self.index(x, y)]
+        };
+
+        // cost[visited][last] is the length of the shortest route from the
+        // start through the checkpoints in the visited set, ending at the
+        // last of them, and from[visited][last] the checkpoint before it.
+        let n = cells.len();
+        let mut cost: Vec<Vec<u32>> = vec![vec![u32::MAX; n]; 1 << n];
+        let mut from: Vec<Vec<usize>> = vec![vec![0; n]; 1 << n];
+        for last in 0..n {
+            cost[1 << last][last] = distance(0, last + 1);
+        }
+        for visited in 1..(1 << n) {
+            for last in (0..n).filter(|last| visited & 1 << last != 0) {
+                let before = visited & !(1 << last);
+                for previous in (0..n).filter(|previous| before & 1 << previous != 0) {
+                    let length = cost[before][previous].saturating_add(distance(previous + 1, last + 1));
+                    if length < cost[visited][last] {
+                        cost[visited][last] = length;
+                        from[visited][last] = previous;
+                    }
+                }
+            }
+        }
+
+        // Work back from the best last checkpoint to find the order.
+        let all = (1 << n) - 1;
+        let mut order: Vec<usize> = vec![n + 1];
+        if n > 0 {
+            let mut last = (0..n).min_by_key(|last| cost[all][*last].saturating_add(distance(last + 1, n + 1))).unwrap();
+            let mut visited = all;
+            while visited != 0 {
+                order.push(last + 1);
+                let previous = from[visited][last];
+                visited &= !(1 << last);
+                last = previous;
+            }
+        }
+        order.push(0);
+        order.reverse();
+
+        let mut tour: Vec<(u32, u32)> = vec![stops[0]];
+        for leg in order.windows(2) {
+            tour.extend(self.get_route(&searches[leg[0]], stops[leg[1]]).into_iter().skip(1));
+        }
+        tour
+    }
+
+    pub fn search_from(&self, from: (u32, u32)) -> Search {
+        let mut search = Search {
+            distances: vec![u32::MAX; self.flags.len()],
+            previous: vec![0; self.flags.len()]
+        };
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        let start = self.index(from.0, from.1);
+        search.distances[start] = 0;
+        queue.push_back(start);
+
+        while let Some(index) = queue.pop_front() {
+            for next in self.get_moves(&self.get_cell_at(index)) {
+                let next_index = self.index(next.x, next.y);
+                if search.distances[next_index] == u32::MAX {
+                    search.distances[next_index] = search.distances[index] + 1;
+                    search.previous[next_index] = index;
+                    queue.push_back(next_index);
+                }
+            }
+        }
+
+        search
+    }
+
+    // The cells from the search's starting cell to the given cell.
+    pub fn get_route(&self, search: &Search, to: (u32, u32)) -> Vec<(u32, u32)> {
+        let mut index = self.index(to.0, to.1);
+        let mut route: Vec<(u32, u32)> = Vec::new();
+        loop {
+            let cell = self.get_cell_at(index);
+            route.push((cell.x, cell.y));
+            if search.distances[index] == 0 {
+                break;
+            }
+            index = search.previous[index];
+        }
+        route.reverse();
+        route
+    }
+
+    // A coin in each checkpoint cell.
+    pub fn get_checkpoint_icons(&self, doc: &Document) -> Vec<Line> {
+        let checkpoints = match &self.checkpoints {
+            Some(checkpoints) => checkpoints,
+            None => return Vec::new()
+        };
+
+        let (x_size, y_size) = (self.x_size as i32, self.y_size as i32);
+        let cell_pt = (transform_x(1.0, x_size, doc).0 - transform_x(0.0, x_size, doc).0)
+            .min(transform_y(1.0, y_size, doc).0 - transform_y(0.0, y_size, doc).0);
+        let offset = doc.line_width_pt / 2.0;
+
+        checkpoints.cells.iter()
+            .map(|(x, y)| Line {
+                points: calculate_points_for_circle(
+                    Pt(cell_pt * COIN_RADIUS),
+                    Pt(transform_x(*x as f64 + 0.5, x_size, doc).0 + offset),
+                    Pt(transform_y(*y as f64 + 0.5, y_size, doc).0 + offset)),
+                is_closed: true,
+                has_fill: true,
+                has_stroke: false,
+                is_clipping_path: false,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Maze;
+
+    // The solution runs along the bottom row and up the right-hand column,
+    // and every other column is a dead end three cells high.
+    fn comb() -> Maze {
+        let mut paths: Vec<Vec<(u32, u32)>> = vec![
+            vec![(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0), (6, 0), (6, 1), (6, 2), (6, 3)]
+        ];
+        paths.extend((0..6).map(|x| (0..4).map(|y| (x, y)).collect()));
+
+        let paths: Vec<&[(u32, u32)]> = paths.iter().map(Vec::as_slice).collect();
+        Maze::carve(7, 4, 1, &paths)
+    }
+
+    #[test]
+    fn tour_takes_the_checkpoints_in_the_shortest_order() {
+        let maze = comb();
+        let tour = maze.get_tour(&[(5, 3), (1, 3), (3, 3)]);
+
+        // Nine moves along the solution, and six up and back down each dead
+        // end, in order from the start.
+        assert_eq!(tour.len() - 1, 27);
+        let tips: Vec<usize> = [(1, 3), (3, 3), (5, 3)].iter()
+            .map(|tip| tour.iter().position(|cell| cell == tip).unwrap())
+            .collect();
+        assert!(tips[0] < tips[1] && tips[1] < tips[2]);
+        assert_eq!(tour[tour.len() - 1], (6, 3));
+    }
+
+    #[test]
+    fn checkpoints_are_scattered_outside_the_start_and_finish() {
+        let mut maze = comb();
+        let moves = maze.add_checkpoints(5);
+
+        let checkpoints = maze.checkpoints.as_ref().unwrap();
+        let tour = &checkpoints.tour;
+        assert_eq!(tour.len() - 1, moves);
+        assert_eq!(tour[0], (0, 0));
+        for (index, cell) in checkpoints.cells.iter().enumerate() {
+            assert!(!checkpoints.cells[..index].contains(cell));
+            assert!(*cell != (0, 0) && *cell != (6, 3));
+            assert!(tour.contains(cell), "the tour misses {:?}", cell);
+        }
+
+        // Every step of the tour is a move through the maze.
+        for step in tour.windows(2) {
+            let cell = maze.get_cell(step[0].0, step[0].1);
+            assert!(maze.get_moves(&cell).iter().any(|next| (next.x, next.y) == step[1]));
+        }
+    }
+}
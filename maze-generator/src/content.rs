@@ -0,0 +1,168 @@
+// Page content streamed past printpdf.  printpdf keeps every point of every
+// path as an operation until the document is saved, which for the walls of
+// a 5000 x 5000 maze is more memory than most machines have.  The walls are
+// written here instead, already compressed, and spliced into their page after
+// it is saved: the layer gets a marker where they belong, and the page's
+// content is split around the marker into an array of streams with the walls
+// in between.  Streams in a page's content are read as one, so the walls are
+// drawn in the same place, with the same graphics state, as before.
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use printpdf::{Line, PdfLayerReference};
+use std::io::{self, BufWriter, Write};
+
+use crate::update::{self, Update};
+
+// The operator of the markers.  Its operand is the number of the stream.
+// Word spacing is never set otherwise, and the marker does not stay in the
+// page.
+const MARKER: &[u8] = b" Tw\n";
+
+pub struct StreamedContent {
+    // Buffered, as the compressor is slow with the short writes of each point
+    streams: Vec<BufWriter<ZlibEncoder<Vec<u8>>>>
+}
+
+impl StreamedContent {
+    pub fn new() -> StreamedContent {
+        StreamedContent {
+            streams: Vec::new()
+        }
+    }
+
+    // Start a stream at this point of the layer.  Shapes added from now on
+    // go in it.
+    pub fn begin(&mut self, layer: &PdfLayerReference) {
+        layer.set_word_spacing(self.streams.len() as f64);
+        self.streams.push(BufWriter::new(ZlibEncoder::new(Vec::new(), Compression::fast())));
+    }
+
+    // Add a line, as layer.add_shape() would.  Only stroked lines, such as
+    // walls, are supported.
+    pub fn add_shape(&mut self, line: &Line) {
+        let stream = self.streams.last_mut().expect("a stream begun");
+        for (index, (point, _)) in line.points.iter().enumerate() {
+            let operator = if index == 0 { "m" } else { "l" };
+            writeln!(stream, "{:.2} {:.2} {}", point.x.0, point.y.0, operator).unwrap();
+        }
+        stream.write_all(if line.is_closed { b"s\n" } else { b"S\n" }).unwrap();
+    }
+
+    // Splice the streams into the pages of the saved document.
+    pub fn add_to(self, update: &mut Update) -> io::Result<()> {
+        if self.streams.is_empty() {
+            return Ok(());
+        }
+        let mut streams: Vec<Option<Vec<u8>>> = self.streams.into_iter()
+            .map(|stream| stream.into_inner().map_err(|error| error.into_error())?.finish().map(Some))
+            .collect::<io::Result<_>>()?;
+
+        let root = update.trailer_reference("/Root")?;
+        let catalog = update.object(root)?;
+        let pages = update::key_number(&catalog, b"/Pages").ok_or_else(|| update::invalid("no pages"))?;
+        let kids = update.object(pages)?;
+
+        for page in update::references(&kids, b"/Kids") {
+            let page_object = update.object(page)?;
+            let contents = update::key_number(&page_object, b"/Contents")
+                .ok_or_else(|| update::invalid("a page without content"))?;
+            let content = update::stream_content(&update.object(contents)?)?;
+
+            let mut pieces = split_at_markers(&content).into_iter();
+            let (first, _) = match pieces.next() {
+                Some(piece) => piece,
+                None => continue
+            };
+            update.replace(contents, update::stream_object("", first));
+            let mut references = vec![contents];
+
+            for (piece, index) in pieces {
+                let stream = index.and_then(|index| streams.get_mut(index)).and_then(Option::take)
+                    .ok_or_else(|| update::invalid("an unknown marker"))?;
+                references.push(update.add(update::stream_object("/Filter/FlateDecode", &stream)));
+                references.push(update.add(update::stream_object("", piece)));
+            }
+
+            let array: Vec<String> = references.iter().map(|id| format!("{} 0 R", id)).collect();
+            let dictionary = update::dictionary(&page_object).ok_or_else(|| update::invalid("a damaged page"))?;
+            update.replace(page, update::with_value(&dictionary, b"/Contents", &format!("[{}]", array.join(" "))));
+        }
+
+        if streams.iter().any(Option::is_some) {
+            return Err(update::invalid("a stream without a marker"));
+        }
+        Ok(())
+    }
+}
+
+// The content before the first marker, if there are any, and the content
+// after each marker, with the number of its stream.
+fn split_at_markers(content: &[u8]) -> Vec<(&[u8], Option<usize>)> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut index = None;
+    while let Some(at) = update::find(&content[start..], MARKER).map(|at| start + at) {
+        let line = content[..at].iter().rposition(|&byte| byte == b'\n').map_or(0, |newline| newline + 1);
+        pieces.push((&content[start..line], index));
+        index = std::str::from_utf8(&content[line..at]).ok()
+            .and_then(|operand| operand.parse::<f64>().ok())
+            .map(|operand| operand as usize);
+        start = at + MARKER.len();
+    }
+
+    if !pieces.is_empty() {
+        pieces.push((&content[start..], index));
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_at_markers;
+    use crate::{Caption, Document, Maze, Theme};
+    use std::{fs, process};
+
+    #[test]
+    fn content_splits_around_each_marker() {
+        let content = b"/OC /MC1 BDC\nq\n0.00 Tw\n2 J\n1.00 Tw\nQ\nEMC\n";
+        let pieces = split_at_markers(content);
+        assert_eq!(pieces, vec![
+            (&b"/OC /MC1 BDC\nq\n"[..], None),
+            (&b"2 J\n"[..], Some(0)),
+            (&b"Q\nEMC\n"[..], Some(1))
+        ]);
+
+        assert!(split_at_markers(b"q\n(Tw) Tj\nQ\n").is_empty());
+    }
+
+    #[test]
+    fn walls_are_spliced_into_the_saved_page() {
+        let mut maze = Maze::new(12, 8, 1);
+        maze.generate();
+        let doc = Document {
+            page_height_in: 11.0,
+            page_width_in: 8.5,
+            line_width_pt: 1.0,
+            margin_in: 0.5,
+            theme: Theme::new(),
+            blocks: None,
+            caption: Caption::new()
+        };
+
+        let path = std::env::temp_dir().join(format!("maze-content-{}.pdf", process::id()));
+        let path = path.to_str().unwrap();
+        maze.to_pdf(&doc, false, path);
+        let pdf = lopdf::Document::load(path);
+        fs::remove_file(path).unwrap();
+        let pdf = pdf.unwrap();
+
+        let page = *pdf.get_pages().values().next().unwrap();
+        assert_eq!(pdf.get_page_contents(page).len(), 3);
+
+        let content = String::from_utf8(pdf.get_page_content(page).unwrap()).unwrap();
+        assert!(!content.contains(" Tw\n"));
+        let strokes = content.lines().filter(|&line| line == "S" || line == "s").count();
+        assert_eq!(strokes, maze.wall_polylines(-1..13, -1..9).len());
+    }
+}
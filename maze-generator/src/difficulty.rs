@@ -0,0 +1,169 @@
+// Difficulty targets.  Two measures of the solution stand in for how hard a
+// maze is: how winding it is, the number of moves it takes over the number
+// that a straight run from the start to the finish would, and its decisions,
+// the share of its cells that are junctions with more than one way on.
+// Winding solutions with many junctions are harder.
+//
+// To hit a target, mazes are generated with different tunings until one
+// measures within range: a fresh seed each time, together with the chance
+// that a tip splits and the weight of the long east-west corridors in the
+// middle of the maze, chosen from the values below.  Fewer splits make longer,
+// more winding solutions, and more splits make more junctions, so the split
+// chance is steered toward the target while the corridor weight is random.
+
+use rand::prelude::*;
+
+use crate::{Maze, MeanderStrategy};
+
+// The corridor weight used by default
+pub const CORRIDOR_WEIGHT: u32 = 100;
+
+// Values tried when searching for a difficulty
+const SPLIT_CHANCES: [f64; 5] = [0.01, 0.02, 0.05, 0.1, 0.2];
+const CORRIDOR_WEIGHTS: [u32; 4] = [1, 10, 100, 1000];
+
+// Mazes generated in search of the difficulty before settling for the closest
+const DIFFICULTY_ATTEMPTS: u32 = 200;
+
+#[derive(Copy, Clone)]
+pub struct Tuning {
+    pub seed: u64,
+    pub split_chance: f64,
+    pub corridor_weight: u32
+}
+
+impl Tuning {
+    // The default tuning for the given split chance, with a random seed.
+    pub fn new(split_chance: f64) -> Tuning {
+        Tuning {
+            seed: thread_rng().gen(),
+            split_chance,
+            corridor_weight: CORRIDOR_WEIGHT
+        }
+    }
+
+}
+
+#[derive(Copy, Clone)]
+pub struct Difficulty {
+    pub winding: (f64, f64),
+    pub decisions: (f64, f64)
+}
+
+// How winding the solution is, and the share of it that is decisions, for
+// each level of difficulty.
+pub const LEVELS: [(&str, Difficulty); 3] = [
+    ("easy", Difficulty { winding: (1.0, 1.3), decisions: (0.0, 1.0) }),
+    ("medium", Difficulty { winding: (1.5, 2.5), decisions: (0.08, 1.0) }),
+    ("hard", Difficulty { winding: (2.5, 100.0), decisions: (0.08, 1.0) })
+];
+
+impl Difficulty {
+    // How far the measures are outside the target ranges, or 0 if within.
+    fn distance(&self, (winding, decisions): (f64, f64)) -> f64 {
+        let outside = |value: f64, (low, high): (f64, f64)| (low - value).max(value - high).max(0.0) / (high - low);
+        outside(winding, self.winding) + outside(decisions, self.decisions)
+    }
+
+    // The level of difficulty with the given name, if there is one.
+    pub fn named(name: &str) -> Option<Difficulty> {
+        LEVELS.iter().find(|(level, _)| *level == name).map(|(_, difficulty)| *difficulty)
+    }
+}
+
+// The name of the level of difficulty whose ranges the measures are closest
+// to.
+pub fn rate(measures: (f64, f64)) -> &'static str {
+    LEVELS.iter()
+        .min_by(|(_, a), (_, b)| a.distance(measures).total_cmp(&b.distance(measures)))
+        .map(|(level, _)| *level)
+        .unwrap()
+}
+
+impl Maze {
+    pub fn set_tuning(&mut self, tuning: &Tuning) {
+        self.seed = tuning.seed;
+        self.rng = StdRng::seed_from_u64(tuning.seed);
+        self.growth.split_chance = tuning.split_chance;
+        self.strategies[0] = MeanderStrategy::new(1, tuning.corridor_weight, 1, 1, 1, 1);
+    }
+
+    // How winding the solution is, and the share of it that is decisions.
+    pub fn measure_difficulty(&self) -> (f64, f64) {
+        let path = self.solution_path();
+        let decisions = self.count_decisions(&path);
+        // Each flight of stairs between levels is a single move.
+        let straight = self.start_x.abs_diff(self.finish_x)
+            + (self.start_y % self.level_height).abs_diff(self.finish_y % self.level_height)
+            + self.level(self.start_y).abs_diff(self.level(self.finish_y));
+        ((path.len() - 1) as f64 / straight as f64, decisions as f64 / path.len() as f64)
+    }
+}
+
+// Generate mazes built by new_maze() with different tunings until one meets
+// the difficulty.  The split chance starts in the middle of its values and
+// moves one step after each maze that misses: up when the solution is too
+// winding or has too few decisions, and down when it is too straight.
+// Returns the maze, its tuning and measures, and whether it met the
+// difficulty or was only the closest found.
+pub fn generate_for_difficulty<F>(new_maze: F, difficulty: &Difficulty) -> (Maze, Tuning, (f64, f64), bool)
+    where F: Fn(&Tuning) -> Maze {
+    let mut rng = thread_rng();
+    let mut split = SPLIT_CHANCES.len() / 2;
+    let mut best: Option<(f64, Maze, Tuning, (f64, f64))> = None;
+
+    for _ in 0..DIFFICULTY_ATTEMPTS {
+        let tuning = Tuning {
+            seed: rng.gen(),
+            split_chance: SPLIT_CHANCES[split],
+            corridor_weight: *CORRIDOR_WEIGHTS.choose(&mut rng).unwrap()
+        };
+        let mut maze = new_maze(&tuning);
+        maze.generate();
+
+        let measures = maze.measure_difficulty();
+        let distance = difficulty.distance(measures);
+        if distance == 0.0 {
+            return (maze, tuning, measures, true);
+        }
+        if best.as_ref().map_or(true, |(best_distance, _, _, _)| distance < *best_distance) {
+            best = Some((distance, maze, tuning, measures));
+        }
+
+        let (winding, decisions) = measures;
+        if winding > difficulty.winding.1 || decisions < difficulty.decisions.0 {
+            split = (split + 1).min(SPLIT_CHANCES.len() - 1);
+        } else if winding < difficulty.winding.0 {
+            split = split.saturating_sub(1);
+        }
+    }
+
+    let (_, maze, tuning, measures) = best.unwrap();
+    (maze, tuning, measures, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_for_difficulty, rate, LEVELS};
+    use crate::Maze;
+
+    // Every level of difficulty can be met at the default size, with plain,
+    // woven and multi-level mazes.
+    #[test]
+    fn each_level_is_reachable() {
+        for &(levels, weave) in &[(1, false), (1, true), (3, false)] {
+            for (name, difficulty) in LEVELS.iter() {
+                let (maze, _, measures, met) = generate_for_difficulty(|tuning| {
+                    let mut maze = Maze::with_levels(30, 40, levels, 3);
+                    maze.set_tuning(tuning);
+                    maze.set_weave(weave);
+                    maze
+                }, difficulty);
+
+                assert!(met, "{} with {} levels, weave {}: the closest was {:?}", name, levels, weave, measures);
+                assert_eq!(maze.measure_difficulty(), measures);
+                assert_eq!(rate(measures), *name);
+            }
+        }
+    }
+}
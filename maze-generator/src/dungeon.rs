@@ -0,0 +1,201 @@
+// Dungeons.  Before generation, rectangular rooms are placed at random,
+// apart from each other and from the start and finish areas, and the maze is
+// then grown around them as corridors.  A room is open inside, like the start
+// and finish areas.
+//
+// The first tip to reach a room opens it: a tree of edges is drawn through
+// the room from the cell entered, so that tips can backtrack out of it and
+// the solution can cross it, and a few more tips set off from cells along its
+// walls to give the room other doorways.  Rooms are numbered in the PDF, for
+// a game master's key.
+
+use printpdf::*;
+use rand::prelude::*;
+use std::collections::VecDeque;
+
+use crate::topology::DIGIT_WIDTH;
+use crate::{transform_x, transform_y, Cell, Direction, Document, Maze};
+
+// Rooms are at least this many cells on a side
+const MIN_ROOM_SIZE: u32 = 2;
+
+// Largest room, as a fraction of the maze's shorter side
+const MAX_ROOM_FRACTION: f64 = 0.25;
+
+// Tries at placing each room before giving up on it
+const PLACEMENT_TRIES: u32 = 100;
+
+// Tips that set off from a room when it is opened, besides the one that
+// entered it
+const ROOM_EXITS: usize = 3;
+
+pub struct Room {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32
+}
+
+impl Room {
+    // True if the rooms overlap or touch, counting one cell around this one.
+    fn crowds(&self, other: &Room) -> bool {
+        self.x <= other.x + other.width && other.x <= self.x + self.width &&
+            self.y <= other.y + other.height && other.y <= self.y + self.height
+    }
+}
+
+impl Maze {
+    // Place up to the given number of rooms, numbered in the PDF if labels
+    // is set.  Returns the number placed, which may be fewer if the maze is
+    // too crowded.
+    pub fn add_rooms(&mut self, count: usize, labels: bool) -> usize {
+        self.room_labels = labels;
+
+        let max_size = ((self.x_size.min(self.y_size) as f64 * MAX_ROOM_FRACTION) as u32).max(MIN_ROOM_SIZE);
+
+        // The start and finish areas, to keep the rooms clear of them
+        let size = self.start_finish_size;
+        let mut taken = vec![
+            Room { x: 0, y: 0, width: size, height: size },
+            Room { x: self.x_size - size, y: self.y_size - size, width: size, height: size }
+        ];
+
+        for _ in 0..count {
+            for _ in 0..PLACEMENT_TRIES {
+                let width = self.rng.gen_range(MIN_ROOM_SIZE, max_size + 1);
+                let height = self.rng.gen_range(MIN_ROOM_SIZE, max_size + 1);
+                if width + 2 > self.x_size || height + 2 > self.y_size {
+                    continue;
+                }
+
+                let room = Room {
+                    x: self.rng.gen_range(1, self.x_size - width),
+                    y: self.rng.gen_range(1, self.y_size - height),
+                    width,
+                    height
+                };
+                if !taken.iter().any(|other| room.crowds(other)) {
+                    taken.push(room);
+                    break;
+                }
+            }
+        }
+
+        for room in taken.drain(2..) {
+            self.rooms.push(room);
+            let number = self.rooms.len() as u16;
+            let room = self.rooms.last().unwrap();
+            for y in room.y..room.y + room.height {
+                for x in room.x..room.x + room.width {
+                    let index = self.index(x, y);
+                    self.room_cells[index] = number;
+                }
+            }
+        }
+
+        self.rooms.len()
+    }
+
+    // True if both cells are in the same room.
+    pub fn same_room(&self, cell: &Cell, other: &Cell) -> bool {
+        let room = self.room_cells[self.index(cell.x, cell.y)];
+        room != 0 && room == self.room_cells[self.index(other.x, other.y)]
+    }
+
+    // Open up the room that a tip has just entered, if it is in one.
+    // Returns the new tips that set off from its other cells.
+    pub fn open_room(&mut self, entered: &Cell) -> Vec<Cell> {
+        let room = self.room_cells[self.index(entered.x, entered.y)];
+        if room == 0 {
+            return Vec::new();
+        }
+
+        let mut cells: Vec<Cell> = Vec::new();
+        let mut queue: VecDeque<Cell> = VecDeque::new();
+        queue.push_back(*entered);
+        while let Some(cell) = queue.pop_front() {
+            for direction in Direction::ALL.iter() {
+                if let Some(next) = self.get_adjacent(&cell, direction) {
+                    if self.same_room(&cell, &next) && !next.visited() {
+                        self.draw_edge(&cell, direction);
+                        self.mark_as_visited(&next);
+                        let next = self.get_cell(next.x, next.y);
+                        cells.push(next);
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        // Only cells along the walls can lead anywhere.
+        cells.retain(|cell| Direction::ALL.iter()
+            .filter_map(|direction| self.get_adjacent(cell, direction))
+            .any(|next| !self.same_room(cell, &next)));
+        cells.choose_multiple(&mut self.rng, ROOM_EXITS).copied().collect()
+    }
+
+    // Number each room in the middle of its floor.
+    pub fn draw_room_labels(&self, layer: &PdfLayerReference, font: &IndirectFontRef, doc: &Document) {
+        let (x_size, y_size) = (self.x_size as i32, self.y_size as i32);
+        let cell_pt = (transform_x(1.0, x_size, doc).0 - transform_x(0.0, x_size, doc).0)
+            .min(transform_y(1.0, y_size, doc).0 - transform_y(0.0, y_size, doc).0);
+        let size = (cell_pt * 0.8).min(14.0);
+        let offset = doc.line_width_pt / 2.0;
+
+        for (index, room) in self.rooms.iter().enumerate() {
+            let text = (index + 1).to_string();
+            let x = transform_x(room.x as f64 + room.width as f64 / 2.0, x_size, doc).0 + offset -
+                text.len() as f64 * size * DIGIT_WIDTH / 2.0;
+            let y = transform_y(room.y as f64 + room.height as f64 / 2.0, y_size, doc).0 + offset - size * 0.35;
+            layer.use_text(text, size, Mm::from(Pt(x)), Mm::from(Pt(y)), font);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Room, MIN_ROOM_SIZE};
+    use crate::{Direction, Maze};
+    use rand::prelude::*;
+
+    #[test]
+    fn entering_a_room_opens_all_of_it() {
+        // A 3x3 room in the middle of a 5x5 maze, entered from below.
+        let mut maze = Maze::new(5, 5, 1);
+        maze.rooms.push(Room { x: 1, y: 1, width: 3, height: 3 });
+        for y in 1..4 {
+            for x in 1..4 {
+                let index = maze.index(x, y);
+                maze.room_cells[index] = 1;
+            }
+        }
+        maze.mark_as_visited(&maze.get_cell(0, 0));
+        maze.carve_paths(&[&[(0, 0), (1, 0), (1, 1)]]);
+
+        let tips = maze.open_room(&maze.get_cell(1, 1));
+        let room: Vec<(u32, u32)> = (1..4).flat_map(|y| (1..4).map(move |x| (x, y))).collect();
+        assert!(room.iter().all(|(x, y)| maze.get_cell(*x, *y).visited()));
+        let edges: u32 = room.iter().map(|(x, y)| (maze.get_cell(*x, *y).flags & 0x0f).count_ones()).sum();
+        assert_eq!(edges, 8);
+
+        // The floor is open, and new tips leave from its walls.
+        assert!(maze.get_passage(&maze.get_cell(2, 2), &Direction::East).is_some());
+        assert!(maze.get_passage(&maze.get_cell(3, 3), &Direction::East).is_none());
+        assert_eq!(tips.len(), 3);
+        assert!(tips.iter().all(|tip| room.contains(&(tip.x, tip.y)) && (tip.x, tip.y) != (2, 2) && (tip.x, tip.y) != (1, 1)));
+    }
+
+    #[test]
+    fn rooms_keep_apart_from_each_other_and_the_areas() {
+        let mut maze = Maze::new(20, 20, 3);
+        maze.rng = StdRng::seed_from_u64(1);
+        let placed = maze.add_rooms(6, false);
+        assert!(placed > 1);
+
+        let areas = [Room { x: 0, y: 0, width: 3, height: 3 }, Room { x: 17, y: 17, width: 3, height: 3 }];
+        for (index, room) in maze.rooms.iter().enumerate() {
+            assert!((MIN_ROOM_SIZE..=5).contains(&room.width) && (MIN_ROOM_SIZE..=5).contains(&room.height));
+            assert!(!areas.iter().chain(maze.rooms[..index].iter()).any(|other| room.crowds(other)));
+        }
+    }
+}
@@ -0,0 +1,255 @@
+// Graph output.  Cells are written as nodes and the open passages between
+// neighbouring cells as undirected edges, for use with graph analysis and
+// drawing tools.  The start and finish areas are open inside, so their cells
+// are joined to each other as well.  In a weave maze, a tunnel joins the
+// cells at either end of it.  Nodes in the start and finish areas, and
+// the nodes and edges along the solution, are marked.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::{Direction, Maze};
+
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+    Json
+}
+
+impl GraphFormat {
+    pub fn filename(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "maze.dot",
+            GraphFormat::GraphMl => "maze.graphml",
+            GraphFormat::Json => "maze.json"
+        }
+    }
+}
+
+// Nodes are numbered row by row from the lower-left cell, the same order
+// the maze stores its cells in.
+struct Node {
+    x: u32,
+    y: u32,
+    start: bool,
+    finish: bool,
+    solution: bool
+}
+
+struct Edge {
+    from: usize,
+    to: usize,
+    solution: bool
+}
+
+struct MazeGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    solution: Vec<usize>
+}
+
+impl Maze {
+    // Write the graph.  Returns the number of nodes and edges written.
+    pub fn to_graph(&self, format: &GraphFormat, filename: &str) -> (usize, usize) {
+        let graph = self.get_graph();
+
+        let mut out = BufWriter::new(File::create(filename).unwrap());
+        match format {
+            GraphFormat::Dot => write_dot(&mut out, &graph),
+            GraphFormat::GraphMl => write_graphml(&mut out, &graph),
+            GraphFormat::Json => write_json(&mut out, &graph)
+        }.unwrap();
+
+        (graph.nodes.len(), graph.edges.len())
+    }
+
+    fn get_graph(&self) -> MazeGraph {
+        let mut nodes: Vec<Node> = Vec::with_capacity(self.flags.len());
+        for y in 0..self.y_size {
+            for x in 0..self.x_size {
+                let cell = self.get_cell(x, y);
+                nodes.push(Node {
+                    x,
+                    y,
+                    start: cell.start_area(),
+                    finish: cell.finish_area(),
+                    solution: false
+                });
+            }
+        }
+
+        let solution: Vec<usize> = self.solution_path().iter()
+            .map(|(x, y)| self.index(*x, *y))
+            .collect();
+        for id in solution.iter() {
+            nodes[*id].solution = true;
+        }
+
+        // Each passage is found once, from its west or south end, or from
+        // its east or north end when it wraps around.  A solution edge joins
+        // consecutive cells of the solution, in either order.
+        let solution_steps: HashSet<(usize, usize)> = solution.windows(2)
+            .map(|step| (step[0].min(step[1]), step[0].max(step[1])))
+            .collect();
+
+        let mut edges: Vec<Edge> = Vec::new();
+        for y in 0..self.y_size {
+            for x in 0..self.x_size {
+                let cell = self.get_cell(x, y);
+                let from = self.index(x, y);
+
+                for direction in [Direction::East, Direction::North].iter() {
+                    if let Some(next) = self.get_passage(&cell, direction) {
+                        let to = self.index(next.x, next.y);
+                        edges.push(Edge { from, to, solution: solution_steps.contains(&(from.min(to), from.max(to))) });
+                    }
+                }
+            }
+        }
+
+        MazeGraph {
+            nodes,
+            edges,
+            solution
+        }
+    }
+}
+
+// GraphViz DOT.  Node positions are pinned to the cell grid, so
+// 'neato -n -Tpdf maze.dot' draws the graph in the shape of the maze.
+// Start cells are green, finish cells red and the solution orange.
+fn write_dot<W: Write>(out: &mut W, graph: &MazeGraph) -> std::io::Result<()> {
+    writeln!(out, "graph maze {{")?;
+    writeln!(out, "  node [shape=point, width=0.1];")?;
+
+    for (id, node) in graph.nodes.iter().enumerate() {
+        let color = if node.start {
+            "green"
+        } else if node.finish {
+            "red"
+        } else if node.solution {
+            "orange"
+        } else {
+            "black"
+        };
+
+        writeln!(out, "  {} [pos=\"{},{}\", start={}, finish={}, solution={}, color={}];",
+            id, node.x * 36, node.y * 36, node.start, node.finish, node.solution, color)?;
+    }
+
+    for edge in graph.edges.iter() {
+        if edge.solution {
+            writeln!(out, "  {} -- {} [solution=true, color=orange, penwidth=3];", edge.from, edge.to)?;
+        } else {
+            writeln!(out, "  {} -- {};", edge.from, edge.to)?;
+        }
+    }
+    writeln!(out, "}}")?;
+
+    Ok(())
+}
+
+fn write_graphml<W: Write>(out: &mut W, graph: &MazeGraph) -> std::io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">")?;
+    writeln!(out, "  <key id=\"x\" for=\"node\" attr.name=\"x\" attr.type=\"int\"/>")?;
+    writeln!(out, "  <key id=\"y\" for=\"node\" attr.name=\"y\" attr.type=\"int\"/>")?;
+    writeln!(out, "  <key id=\"start\" for=\"node\" attr.name=\"start\" attr.type=\"boolean\"/>")?;
+    writeln!(out, "  <key id=\"finish\" for=\"node\" attr.name=\"finish\" attr.type=\"boolean\"/>")?;
+    writeln!(out, "  <key id=\"solution\" for=\"all\" attr.name=\"solution\" attr.type=\"boolean\"/>")?;
+    writeln!(out, "  <graph id=\"maze\" edgedefault=\"undirected\">")?;
+
+    for (id, node) in graph.nodes.iter().enumerate() {
+        writeln!(out, "    <node id=\"n{}\"><data key=\"x\">{}</data><data key=\"y\">{}</data>\
+            <data key=\"start\">{}</data><data key=\"finish\">{}</data><data key=\"solution\">{}</data></node>",
+            id, node.x, node.y, node.start, node.finish, node.solution)?;
+    }
+
+    for edge in graph.edges.iter() {
+        writeln!(out, "    <edge source=\"n{}\" target=\"n{}\"><data key=\"solution\">{}</data></edge>",
+            edge.from, edge.to, edge.solution)?;
+    }
+
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")?;
+
+    Ok(())
+}
+
+// An adjacency list.  Each node lists the ids of the nodes it is joined to,
+// and the solution is given as a list of node ids from start to finish.
+fn write_json<W: Write>(out: &mut W, graph: &MazeGraph) -> std::io::Result<()> {
+    let mut adjacent: Vec<Vec<usize>> = vec![Vec::new(); graph.nodes.len()];
+    for edge in graph.edges.iter() {
+        adjacent[edge.from].push(edge.to);
+        adjacent[edge.to].push(edge.from);
+    }
+
+    let solution: Vec<String> = graph.solution.iter().map(|id| id.to_string()).collect();
+
+    writeln!(out, "{{")?;
+    writeln!(out, "  \"solution\": [{}],", solution.join(", "))?;
+    writeln!(out, "  \"nodes\": [")?;
+
+    for (id, node) in graph.nodes.iter().enumerate() {
+        let mut neighbours = adjacent[id].clone();
+        neighbours.sort_unstable();
+        let neighbours: Vec<String> = neighbours.iter().map(|id| id.to_string()).collect();
+
+        writeln!(out, "    {{\"id\": {}, \"x\": {}, \"y\": {}, \"start\": {}, \"finish\": {}, \"solution\": {}, \"adjacent\": [{}]}}{}",
+            id, node.x, node.y, node.start, node.finish, node.solution, neighbours.join(", "),
+            if id + 1 < graph.nodes.len() { "," } else { "" })?;
+    }
+
+    writeln!(out, "  ]")?;
+    writeln!(out, "}}")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_json;
+    use crate::Maze;
+
+    // A 3x2 maze: the solution runs along the bottom row and up into the
+    // finish, and a branch climbs from the start to the middle of the top.
+    fn branching() -> Maze {
+        Maze::carve(3, 2, 1, &[&[(0, 0), (1, 0), (2, 0), (2, 1)], &[(0, 0), (0, 1), (1, 1)]])
+    }
+
+    #[test]
+    fn edges_are_the_passages_with_the_solution_marked() {
+        let graph = branching().get_graph();
+
+        assert_eq!(graph.nodes.len(), 6);
+        assert_eq!(graph.solution, vec![0, 1, 2, 5]);
+        let solution: Vec<bool> = graph.nodes.iter().map(|node| node.solution).collect();
+        assert_eq!(solution, vec![true, true, true, false, false, true]);
+        assert!(graph.nodes[0].start && graph.nodes[5].finish);
+
+        let edges: Vec<(usize, usize, bool)> = graph.edges.iter().map(|edge| (edge.from, edge.to, edge.solution)).collect();
+        assert_eq!(edges, vec![(0, 1, true), (0, 3, false), (1, 2, true), (2, 5, true), (3, 4, false)]);
+    }
+
+    #[test]
+    fn json_lists_each_node_with_its_neighbours() {
+        let mut json = Vec::new();
+        write_json(&mut json, &branching().get_graph()).unwrap();
+
+        assert_eq!(String::from_utf8(json).unwrap(), "\
+{
+  \"solution\": [0, 1, 2, 5],
+  \"nodes\": [
+    {\"id\": 0, \"x\": 0, \"y\": 0, \"start\": true, \"finish\": false, \"solution\": true, \"adjacent\": [1, 3]},
+    {\"id\": 1, \"x\": 1, \"y\": 0, \"start\": false, \"finish\": false, \"solution\": true, \"adjacent\": [0, 2]},
+    {\"id\": 2, \"x\": 2, \"y\": 0, \"start\": false, \"finish\": false, \"solution\": true, \"adjacent\": [1, 5]},
+    {\"id\": 3, \"x\": 0, \"y\": 1, \"start\": false, \"finish\": false, \"solution\": false, \"adjacent\": [0, 4]},
+    {\"id\": 4, \"x\": 1, \"y\": 1, \"start\": false, \"finish\": false, \"solution\": false, \"adjacent\": [3]},
+    {\"id\": 5, \"x\": 2, \"y\": 1, \"start\": false, \"finish\": true, \"solution\": true, \"adjacent\": [2]}
+  ]
+}
+");
+    }
+}
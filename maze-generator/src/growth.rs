@@ -0,0 +1,179 @@
+// Growth of the maze.  generate() grows passages from a list of tips, each
+// of which either moves on to an unvisited neighbour or, at a dead end,
+// backtracks along its own passage.  Three settings shape the texture:
+//
+// The split chance is how often a tip that moves on leaves a copy of itself
+// behind, to grow a branch from there later.  Rarely splitting leaves a few
+// tips to wander far, for a long and winding solution.  Often splitting sets
+// many tips growing side by side, in straighter passages, and the solution
+// runs fairly directly to the finish.
+//
+// The maximum number of tips caps how many grow at once; further splits are
+// dropped until a tip is used up.  The fewer the tips, the longer the
+// solution and the fewer the dead ends.  A single tip makes a recursive
+// backtracker, one long passage with the rest of the maze branching off it.
+//
+// The schedule is the order in which tips move.  Round robin moves each tip
+// once per pass, so the tips spread out together, and random order is much
+// the same.  Oldest first keeps moving the first tip until it is used up,
+// which is like having a single tip.  Newest first keeps moving the latest
+// branch, so the solution is as long, but many more short dead ends turn off
+// it.
+
+use rand::prelude::*;
+
+use crate::{Cell, Maze};
+
+// The chance of a tip splitting at each step, by default
+pub const SPLIT_CHANCE: f64 = 0.05;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum Schedule {
+    RoundRobin,
+    Random,
+    OldestFirst,
+    NewestFirst
+}
+
+impl Schedule {
+    // The schedule's name, as entered at the prompt.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Schedule::RoundRobin => "round-robin",
+            Schedule::Random => "random",
+            Schedule::OldestFirst => "oldest",
+            Schedule::NewestFirst => "newest"
+        }
+    }
+
+    // The schedule with the given name, if there is one.
+    pub fn named(name: &str) -> Option<Schedule> {
+        match name {
+            "round-robin" => Some(Schedule::RoundRobin),
+            "random" => Some(Schedule::Random),
+            "oldest" => Some(Schedule::OldestFirst),
+            "newest" => Some(Schedule::NewestFirst),
+            _ => None
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Growth {
+    pub split_chance: f64,
+    pub max_tips: Option<usize>,
+    pub schedule: Schedule
+}
+
+impl Growth {
+    pub fn new() -> Growth {
+        Growth {
+            split_chance: SPLIT_CHANCE,
+            max_tips: None,
+            schedule: Schedule::RoundRobin
+        }
+    }
+}
+
+impl Maze {
+    pub fn set_growth(&mut self, growth: Growth) {
+        self.growth = growth;
+    }
+
+    // The index of the tip to move next, given the one after the tip just
+    // moved, which round robin takes.
+    #[inline]
+    pub fn next_tip(&mut self, tips: &[Cell], index: usize) -> usize {
+        match self.growth.schedule {
+            Schedule::RoundRobin if index < tips.len() => index,
+            Schedule::RoundRobin => 0,
+            Schedule::Random => self.rng.gen_range(0, tips.len()),
+            Schedule::OldestFirst => 0,
+            Schedule::NewestFirst => tips.len() - 1
+        }
+    }
+
+    // Add new tips, as far as the maximum allows.
+    pub fn add_tips<I: IntoIterator<Item = Cell>>(&self, tips: &mut Vec<Cell>, new_tips: I) {
+        let room = self.growth.max_tips.map_or(usize::MAX, |max_tips| max_tips.saturating_sub(tips.len()));
+        tips.extend(new_tips.into_iter().take(room));
+    }
+
+    // Remove a used up tip.  Oldest and newest first need the tips kept in
+    // the order they were added; otherwise the last tip takes its place.
+    pub fn remove_tip(&self, tips: &mut Vec<Cell>, index: usize) {
+        match self.growth.schedule {
+            Schedule::OldestFirst | Schedule::NewestFirst => {
+                tips.remove(index);
+            },
+            _ => {
+                tips.swap_remove(index);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Growth, Schedule};
+    use crate::{Cell, Maze};
+    use rand::prelude::*;
+
+    fn with_schedule(schedule: Schedule, max_tips: Option<usize>) -> Maze {
+        let mut maze = Maze::new(12, 10, 1);
+        maze.set_growth(Growth { schedule, max_tips, ..Growth::new() });
+        maze
+    }
+
+    fn positions(tips: &[Cell]) -> Vec<(u32, u32)> {
+        tips.iter().map(|tip| (tip.x, tip.y)).collect()
+    }
+
+    #[test]
+    fn schedules_pick_and_remove_tips_in_order() {
+        let maze = Maze::new(4, 1, 1);
+        let tips: Vec<Cell> = (0..4).map(|x| maze.get_cell(x, 0)).collect();
+
+        let mut round_robin = with_schedule(Schedule::RoundRobin, None);
+        assert_eq!(round_robin.next_tip(&tips, 2), 2);
+        assert_eq!(round_robin.next_tip(&tips, 4), 0);
+        assert_eq!(with_schedule(Schedule::OldestFirst, None).next_tip(&tips, 2), 0);
+        assert_eq!(with_schedule(Schedule::NewestFirst, None).next_tip(&tips, 2), 3);
+
+        let mut kept = tips.clone();
+        with_schedule(Schedule::NewestFirst, None).remove_tip(&mut kept, 1);
+        assert_eq!(positions(&kept), vec![(0, 0), (2, 0), (3, 0)]);
+        let mut swapped = tips.clone();
+        round_robin.remove_tip(&mut swapped, 1);
+        assert_eq!(positions(&swapped), vec![(0, 0), (3, 0), (2, 0)]);
+    }
+
+    #[test]
+    fn new_tips_stop_at_the_maximum() {
+        let maze = Maze::new(4, 1, 1);
+        let mut tips: Vec<Cell> = vec![maze.get_cell(0, 0)];
+        with_schedule(Schedule::RoundRobin, Some(3)).add_tips(&mut tips, (1..4).map(|x| maze.get_cell(x, 0)));
+        assert_eq!(positions(&tips), vec![(0, 0), (1, 0), (2, 0)]);
+        with_schedule(Schedule::RoundRobin, None).add_tips(&mut tips, Some(maze.get_cell(3, 0)));
+        assert_eq!(tips.len(), 4);
+    }
+
+    #[test]
+    fn every_schedule_grows_a_perfect_maze() {
+        let schedules = [Schedule::RoundRobin, Schedule::Random, Schedule::OldestFirst, Schedule::NewestFirst];
+        for (seed, schedule) in schedules.iter().enumerate() {
+            for max_tips in [None, Some(1)].iter() {
+                let mut maze = with_schedule(*schedule, *max_tips);
+                maze.rng = StdRng::seed_from_u64(seed as u64);
+                maze.generate();
+
+                // Every cell but the start is reached, by one passage each.
+                let cells = (maze.x_size * maze.y_size) as usize;
+                assert!((1..cells).all(|index| maze.get_cell_at(index).visited()));
+                let edges: u32 = (0..cells).map(|index| (maze.get_cell_at(index).flags & 0x0f).count_ones()).sum();
+                assert_eq!(edges as usize, cells - 1);
+                assert_eq!(maze.solution_path().first(), Some(&(maze.start_x, maze.start_y)));
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+// Distance heat maps.  Every cell is filled with a color from a palette
+// according to how many moves it is from the start, so the passages show as
+// bands of color flowing outward through the maze, and the finish sits in
+// whichever band its distance falls in.
+//
+// The heat map can be kept to the neighbourhood of the solution, the cells
+// within a few moves of it, which leaves the rest of the maze white and
+// shows the solution as a ribbon of color with its nearest dead ends.
+//
+// Distances are rounded to a fixed number of shades, and runs of cells in
+// a row with the same shade are filled as one rectangle, which keeps the
+// PDF small.
+
+use printpdf::*;
+use std::collections::VecDeque;
+
+use crate::{transform_x, transform_y, Document, Maze};
+
+// Shades the distances are rounded to
+const SHADES: u32 = 64;
+
+#[derive(Copy, Clone)]
+pub enum Palette {
+    Rainbow,
+    Fire,
+    Ocean,
+    Gray
+}
+
+impl Palette {
+    // The palette's name, as entered at the prompt.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Palette::Rainbow => "rainbow",
+            Palette::Fire => "fire",
+            Palette::Ocean => "ocean",
+            Palette::Gray => "gray"
+        }
+    }
+
+    // The palette with the given name, if there is one.
+    pub fn named(name: &str) -> Option<Palette> {
+        match name {
+            "rainbow" => Some(Palette::Rainbow),
+            "fire" => Some(Palette::Fire),
+            "ocean" => Some(Palette::Ocean),
+            "gray" => Some(Palette::Gray),
+            _ => None
+        }
+    }
+
+    // Colors evenly spaced from the start to the furthest cell
+    fn stops(&self) -> &'static [(f64, f64, f64)] {
+        match self {
+            Palette::Rainbow => &[(1.0, 0.2, 0.2), (1.0, 0.6, 0.1), (1.0, 0.95, 0.2), (0.3, 0.85, 0.3), (0.2, 0.5, 1.0), (0.6, 0.3, 0.9)],
+            Palette::Fire => &[(1.0, 1.0, 0.85), (1.0, 0.9, 0.3), (1.0, 0.55, 0.1), (0.9, 0.2, 0.1), (0.55, 0.05, 0.1)],
+            Palette::Ocean => &[(0.85, 1.0, 1.0), (0.45, 0.85, 0.9), (0.15, 0.55, 0.8), (0.05, 0.25, 0.55)],
+            Palette::Gray => &[(0.95, 0.95, 0.95), (0.45, 0.45, 0.45)]
+        }
+    }
+
+    // The color a given fraction of the way along the palette.
+    fn color(&self, fraction: f64) -> Color {
+        let stops = self.stops();
+        let position = fraction.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+        let index = (position as usize).min(stops.len() - 2);
+        let t = position - index as f64;
+        let (r0, g0, b0) = stops[index];
+        let (r1, g1, b1) = stops[index + 1];
+        Color::Rgb(Rgb::new(r0 + (r1 - r0) * t, g0 + (g1 - g0) * t, b0 + (b1 - b0) * t, None))
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct HeatMap {
+    pub palette: Palette,
+
+    // If set, only cells within this many moves of the solution are colored
+    pub neighbourhood: Option<u32>
+}
+
+impl Maze {
+    pub fn set_heat_map(&mut self, heat_map: Option<HeatMap>) {
+        self.heat_map = heat_map;
+    }
+
+    // The moves from each cell to the nearest cell of the solution.
+    fn distances_from_solution(&self) -> Vec<u32> {
+        let mut distances = vec![u32::MAX; self.flags.len()];
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        for (x, y) in self.solution_path() {
+            let index = self.index(x, y);
+            distances[index] = 0;
+            queue.push_back(index);
+        }
+
+        while let Some(index) = queue.pop_front() {
+            for next in self.get_moves(&self.get_cell_at(index)) {
+                let next_index = self.index(next.x, next.y);
+                if distances[next_index] == u32::MAX {
+                    distances[next_index] = distances[index] + 1;
+                    queue.push_back(next_index);
+                }
+            }
+        }
+
+        distances
+    }
+
+    // Fill the cells with their colors, if the maze has a heat map.
+    pub fn draw_heat_map(&self, layer: &PdfLayerReference, doc: &Document) {
+        let heat_map = match &self.heat_map {
+            Some(heat_map) => heat_map,
+            None => return
+        };
+
+        let distances = self.search_from((self.start_x, self.start_y)).distances;
+        let furthest = distances.iter().filter(|distance| **distance != u32::MAX).max().copied().unwrap_or(0).max(1);
+        let nearby = heat_map.neighbourhood.map(|moves| (self.distances_from_solution(), moves));
+
+        let shade = |index: usize| {
+            if distances[index] == u32::MAX {
+                return None;
+            }
+            if let Some((from_solution, moves)) = &nearby {
+                if from_solution[index] > *moves {
+                    return None;
+                }
+            }
+            Some(distances[index] as u64 * (SHADES - 1) as u64 / furthest as u64)
+        };
+
+        // Runs of cells with the same shade, gathered by shade
+        let mut runs: Vec<Vec<(u32, u32, u32)>> = vec![Vec::new(); SHADES as usize];
+        for y in 0..self.y_size {
+            let mut x = 0;
+            while x < self.x_size {
+                let run_shade = shade(self.index(x, y));
+                let mut end = x + 1;
+                while end < self.x_size && shade(self.index(end, y)) == run_shade {
+                    end += 1;
+                }
+                if let Some(run_shade) = run_shade {
+                    runs[run_shade as usize].push((x, end, y));
+                }
+                x = end;
+            }
+        }
+
+        layer.save_graphics_state();
+        for (run_shade, runs) in runs.iter().enumerate().filter(|(_, runs)| !runs.is_empty()) {
+            layer.set_fill_color(heat_map.palette.color(run_shade as f64 / (SHADES - 1) as f64));
+            for (x0, x1, y) in runs {
+                layer.add_shape(self.get_run(*x0, *x1, *y, doc));
+            }
+        }
+        layer.restore_graphics_state();
+    }
+
+    // The cells from x0 up to x1 in row y, including the walls between and
+    // around them, so that neighbouring runs leave no gap.
+    fn get_run(&self, x0: u32, x1: u32, y: u32, doc: &Document) -> Line {
+        let offset = Pt(doc.line_width_pt / 2.0);
+        let (x_size, y_size) = (self.x_size as i32, self.y_size as i32);
+
+        let pdf_x0 = Mm::from(transform_x(x0 as f64, x_size, doc) + offset);
+        let pdf_y0 = Mm::from(transform_y(y as f64, y_size, doc) + offset);
+        let pdf_x1 = Mm::from(transform_x(x1 as f64, x_size, doc) + offset);
+        let pdf_y1 = Mm::from(transform_y((y + 1) as f64, y_size, doc) + offset);
+
+        Line {
+            points: vec![
+                (Point::new(pdf_x0, pdf_y0), false),
+                (Point::new(pdf_x1, pdf_y0), false),
+                (Point::new(pdf_x1, pdf_y1), false),
+                (Point::new(pdf_x0, pdf_y1), false)
+            ],
+            is_closed: true,
+            has_fill: true,
+            has_stroke: false,
+            is_clipping_path: false
+        }
+    }
+}
@@ -0,0 +1,338 @@
+// Keys and doors.  Locked doors are placed across passages of the solution,
+// spread out along it, and the key to each door is placed in the part of the
+// maze that can only be reached through the door before it, as far down a
+// side branch as possible.  The passages of the maze form a tree, apart from
+// the open floors of rooms, and no door goes inside a room, so there is no
+// way around a door and the keys have to be collected in order.
+//
+// Doors are drawn as heavy red bars marked with a capital letter, and keys
+// as the matching lower case letter.  A solver searches the states (cell,
+// keys held) breadth first to check that the finish can be reached, and the
+// route it finds is drawn as the answer key in the solution PDF.
+
+use printpdf::*;
+use rand::prelude::*;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+
+use crate::{get_wall_line, transform_x, transform_y, Cell, Document, Maze};
+
+const POINTS_PER_INCH: f64 = 72.0;
+
+// Average width of a letter in Helvetica Bold, as a fraction of the font size
+const LETTER_WIDTH: f64 = 0.65;
+
+// How far each end of a door is set back from the walls beside it, as a
+// fraction of the cell size
+const DOOR_INSET: f64 = 0.15;
+
+// The cells on either side of a door, the start side first
+type Door = ((u32, u32), (u32, u32));
+
+pub struct Puzzle {
+    doors: Vec<Door>,
+    keys: Vec<(u32, u32)>,
+
+    // The solver's route from the start cell into the finish area
+    route: Vec<(u32, u32)>
+}
+
+impl Maze {
+    // The solver's route, if doors have been placed.
+    pub fn puzzle_route(&self) -> Option<Vec<(u32, u32)>> {
+        self.puzzle.as_ref().map(|puzzle| puzzle.route.clone())
+    }
+
+    // Place up to the given number of doors along the solution, with their
+    // keys, and check with the solver that the maze can still be solved.
+    // Returns the number of doors placed and the number of moves in the
+    // solver's route, or None if no doors could be placed.
+    pub fn add_doors(&mut self, count: usize) -> Option<(usize, usize)> {
+        let path = self.solution_path();
+
+        let mut doors: Vec<Door> = Vec::new();
+        let mut earliest = 0;
+        for door in 0..count {
+            let target = ((door + 1) * path.len() / (count + 1)).max(earliest);
+            match (target..path.len() - 1).find(|step| self.can_lock(path[*step], path[*step + 1])) {
+                Some(step) => {
+                    doors.push((path[step], path[step + 1]));
+                    earliest = step + 2;
+                },
+                None => break
+            }
+        }
+        if doors.is_empty() {
+            return None;
+        }
+
+        // Each key goes in the cells that open up with the door before it,
+        // as far as possible from the solution.
+        let depth = self.get_distances(&path);
+        let mut keys: Vec<(u32, u32)> = Vec::new();
+        let mut reached: Vec<bool> = vec![false; self.flags.len()];
+        for door in 0..doors.len() {
+            let region = self.get_reachable(&doors, door);
+            let candidates: Vec<usize> = (0..self.flags.len())
+                .filter(|index| region[*index] && !reached[*index])
+                .filter(|index| {
+                    let cell = self.get_cell_at(*index);
+                    !cell.start_area() && !cell.finish_area()
+                })
+                .collect();
+            reached = region;
+
+            let deepest = candidates.iter().map(|index| depth[*index]).max()?;
+            let deepest: Vec<usize> = candidates.into_iter().filter(|index| depth[*index] == deepest).collect();
+            let key = *deepest.choose(&mut self.rng).unwrap();
+            let key = self.get_cell_at(key);
+            keys.push((key.x, key.y));
+        }
+
+        let mut puzzle = Puzzle {
+            doors,
+            keys,
+            route: Vec::new()
+        };
+        puzzle.route = self.solve(&puzzle)?;
+
+        let placed = (puzzle.doors.len(), puzzle.route.len() - 1);
+        self.puzzle = Some(puzzle);
+        Some(placed)
+    }
+
+    // A door can go across an ordinary passage between two cells outside the
+    // start and finish areas.  Tunnels, stairs and passages that wrap around
+    // the edge are left open, as are passages within a room, whose open
+    // floor leads around them.
+    fn can_lock(&self, from: (u32, u32), to: (u32, u32)) -> bool {
+        let from = self.get_cell(from.0, from.1);
+        let to = self.get_cell(to.0, to.1);
+
+        from.x.abs_diff(to.x) + from.y.abs_diff(to.y) == 1 &&
+            !from.start_area() && !from.finish_area() &&
+            !to.start_area() && !to.finish_area() &&
+            !self.same_room(&from, &to)
+    }
+
+    // The number of moves from each cell to the nearest of the given cells,
+    // ignoring doors.
+    fn get_distances(&self, cells: &[(u32, u32)]) -> Vec<u32> {
+        let mut distances: Vec<u32> = vec![u32::MAX; self.flags.len()];
+        let mut queue: VecDeque<Cell> = VecDeque::new();
+        for (x, y) in cells.iter() {
+            distances[self.index(*x, *y)] = 0;
+            queue.push_back(self.get_cell(*x, *y));
+        }
+
+        while let Some(cell) = queue.pop_front() {
+            let distance = distances[self.index(cell.x, cell.y)];
+            for next in self.get_moves(&cell) {
+                let index = self.index(next.x, next.y);
+                if distances[index] == u32::MAX {
+                    distances[index] = distance + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        distances
+    }
+
+    // The cells that can be reached from the start with only the first
+    // `open` doors unlocked.
+    fn get_reachable(&self, doors: &[Door], open: usize) -> Vec<bool> {
+        let mut reached: Vec<bool> = vec![false; self.flags.len()];
+        let mut queue: VecDeque<Cell> = VecDeque::new();
+        reached[self.index(self.start_x, self.start_y)] = true;
+        queue.push_back(self.get_cell(self.start_x, self.start_y));
+
+        while let Some(cell) = queue.pop_front() {
+            for next in self.get_moves(&cell) {
+                let index = self.index(next.x, next.y);
+                let door = find_door(doors, (cell.x, cell.y), (next.x, next.y));
+                if !reached[index] && door.map_or(true, |door| door < open) {
+                    reached[index] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        reached
+    }
+
+    // Breadth first search over the states (cell, keys held), where a key
+    // is picked up by moving onto it and a door can only be passed while
+    // holding its key.  Returns the shortest route into the finish area.
+    fn solve(&self, puzzle: &Puzzle) -> Option<Vec<(u32, u32)>> {
+        let key_count = puzzle.keys.len();
+        let state = |cell: &Cell, held: usize| self.index(cell.x, cell.y) << key_count | held;
+        let pick_up = |cell: &Cell, held: usize| {
+            match puzzle.keys.iter().position(|key| *key == (cell.x, cell.y)) {
+                Some(key) => held | 1 << key,
+                None => held
+            }
+        };
+
+        // The state each reached state was reached from.  Only a few of the
+        // states can be reached, since the keys are taken in order, so they
+        // are kept by state rather than in a table of every state.
+        let mut previous: HashMap<usize, usize> = HashMap::new();
+        let mut queue: VecDeque<(Cell, usize)> = VecDeque::new();
+        let start = self.get_cell(self.start_x, self.start_y);
+        let start_state = state(&start, 0);
+        previous.insert(start_state, start_state);
+        queue.push_back((start, 0));
+
+        while let Some((cell, held)) = queue.pop_front() {
+            if cell.finish_area() {
+                // Walk back through the states to the start
+                let mut route: Vec<(u32, u32)> = Vec::new();
+                let mut current = state(&cell, held);
+                loop {
+                    let cell = self.get_cell_at(current >> key_count);
+                    route.push((cell.x, cell.y));
+                    if current == start_state {
+                        break;
+                    }
+                    current = previous[&current];
+                }
+                route.reverse();
+                return Some(route);
+            }
+
+            for next in self.get_moves(&cell) {
+                if let Some(door) = find_door(&puzzle.doors, (cell.x, cell.y), (next.x, next.y)) {
+                    if held & 1 << door == 0 {
+                        continue;
+                    }
+                }
+
+                let next_held = pick_up(&next, held);
+                let next_state = state(&next, next_held);
+                if let Entry::Vacant(entry) = previous.entry(next_state) {
+                    entry.insert(state(&cell, held));
+                    queue.push_back((next, next_held));
+                }
+            }
+        }
+
+        None
+    }
+
+    // Draw the doors and keys, if any.  The cell coordinates are mapped to
+    // the page by the given document, as for the walls.
+    pub fn draw_puzzle(&self, layer: &PdfLayerReference, font: &IndirectFontRef, doc: &Document) {
+        let puzzle = match &self.puzzle {
+            Some(puzzle) => puzzle,
+            None => return
+        };
+
+        let (x_size, y_size) = (self.x_size as i32, self.y_size as i32);
+        let cell_pt = (transform_x(1.0, x_size, doc).0 - transform_x(0.0, x_size, doc).0)
+            .min(transform_y(1.0, y_size, doc).0 - transform_y(0.0, y_size, doc).0);
+        let size = cell_pt * 0.7;
+        let offset = doc.line_width_pt / 2.0;
+        let letter = |text: String, x: u32, y: u32| {
+            let pdf_x = transform_x(x as f64 + 0.5, x_size, doc).0 + offset - size * LETTER_WIDTH / 2.0;
+            let pdf_y = transform_y(y as f64 + 0.5, y_size, doc).0 + offset - size * 0.35;
+            layer.use_text(text, size, Mm::from(Pt(pdf_x)), Mm::from(Pt(pdf_y)), font);
+        };
+
+        layer.save_graphics_state();
+        let red = Color::Rgb(Rgb::new(0.8, 0.0, 0.0, None));
+        layer.set_fill_color(red.clone());
+        layer.set_outline_color(red);
+        layer.set_outline_thickness((doc.line_width_pt * 3.0).max(1.0));
+        layer.set_line_cap_style(LineCapStyle::Butt);
+
+        for (door, (from, to)) in puzzle.doors.iter().enumerate() {
+            let points = if from.0 != to.0 {
+                let x = from.0.max(to.0) as f64;
+                vec![(x, from.1 as f64 + DOOR_INSET), (x, from.1 as f64 + 1.0 - DOOR_INSET)]
+            } else {
+                let y = from.1.max(to.1) as f64;
+                vec![(from.0 as f64 + DOOR_INSET, y), (from.0 as f64 + 1.0 - DOOR_INSET, y)]
+            };
+            layer.add_shape(get_wall_line(&points, false, x_size, y_size, doc));
+            letter(door_name(door).to_uppercase(), from.0, from.1);
+        }
+
+        for (key, (x, y)) in puzzle.keys.iter().enumerate() {
+            letter(door_name(key), *x, *y);
+        }
+
+        layer.restore_graphics_state();
+    }
+
+    // List the order the keys and doors are taken in across the top margin.
+    pub fn draw_answer_key(&self, layer: &PdfLayerReference, font: &IndirectFontRef, doc: &Document) {
+        let puzzle = match &self.puzzle {
+            Some(puzzle) => puzzle,
+            None => return
+        };
+
+        let steps: Vec<String> = (0..puzzle.doors.len())
+            .map(|door| format!("key {}, door {}", door_name(door), door_name(door).to_uppercase()))
+            .collect();
+        let text = format!("Answer key: {}, then the finish.  {} moves.", steps.join(", "), puzzle.route.len() - 1);
+
+        layer.use_text(
+            text, 8.0,
+            Mm::from(Pt(doc.margin_in * POINTS_PER_INCH)),
+            Mm::from(Pt((doc.page_height_in - doc.margin_in * 0.6) * POINTS_PER_INCH)),
+            font);
+    }
+}
+
+// The door, if any, across the passage between two cells.
+fn find_door(doors: &[Door], from: (u32, u32), to: (u32, u32)) -> Option<usize> {
+    doors.iter().position(|door| *door == (from, to) || *door == (to, from))
+}
+
+fn door_name(door: usize) -> String {
+    ((b'a' + door as u8) as char).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Maze;
+
+    // The solution runs along the bottom row and up the right-hand column.
+    // A side branch before the first door leads three cells up from (2, 0),
+    // and one between the doors two cells up from (4, 0).
+    fn comb() -> Maze {
+        Maze::carve(6, 5, 1, &[
+            &[(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0), (5, 1), (5, 2), (5, 3), (5, 4)],
+            &[(1, 0), (1, 1)],
+            &[(2, 0), (2, 1), (2, 2), (2, 3)],
+            &[(4, 0), (4, 1), (4, 2)]
+        ])
+    }
+
+    #[test]
+    fn keys_go_at_the_end_of_the_branches_behind_each_door() {
+        let mut maze = comb();
+        assert_eq!(maze.add_doors(2), Some((2, 19)));
+
+        let puzzle = maze.puzzle.as_ref().unwrap();
+        assert_eq!(puzzle.doors, vec![((3, 0), (4, 0)), ((5, 1), (5, 2))]);
+        assert_eq!(puzzle.keys, vec![(2, 3), (4, 2)]);
+        assert_eq!(puzzle.route, vec![
+            (0, 0), (1, 0), (2, 0), (2, 1), (2, 2), (2, 3), (2, 2), (2, 1), (2, 0), (3, 0),
+            (4, 0), (4, 1), (4, 2), (4, 1), (4, 0), (5, 0), (5, 1), (5, 2), (5, 3), (5, 4)
+        ]);
+    }
+
+    #[test]
+    fn no_doors_go_in_the_start_and_finish_areas() {
+        // The solution only leaves the areas at (2, 1), so there is no
+        // passage to lock.
+        let mut maze = Maze::carve(4, 4, 2, &[
+            &[(0, 1), (1, 1), (2, 1), (2, 2)],
+            &[(2, 1), (3, 1), (3, 0)]
+        ]);
+        assert_eq!(maze.add_doors(2), None);
+        assert!(maze.puzzle.is_none());
+    }
+}
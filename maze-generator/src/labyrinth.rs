@@ -0,0 +1,437 @@
+// Labyrinths.  A labyrinth has a single path with no branches, leading from
+// the entrance all the way through.  Two kinds are made:
+//
+// Unicursal labyrinths come from a maze of half the size, with every passage
+// split down the middle into two lanes.  Each dead end becomes a U-turn, so
+// the lanes join up into one loop around the maze's walls.  The loop is cut
+// beside the start cell, leaving the entrance and the exit next to each
+// other.
+//
+// Classical labyrinths are the Cretan labyrinth of 7 circuits around the goal
+// and its larger relatives of 11 and 15 circuits.  The path goes once around
+// each circuit, in a fixed order, and only turns at the axis below the goal.
+// Here the circuits are square rings of cells; the turns between them on
+// each side of the axis nest inside each other like brackets, each one
+// taking a column just outside the turns it encloses.
+//
+// Both are laid out on a grid of cells, and drawn with the same line width
+// as the mazes.
+
+use printpdf::*;
+use std::fs::File;
+use std::io::BufWriter;
+use std::ops::Range;
+
+use crate::{get_rectangle, get_star_icon, get_wall_line, Direction, Document, Maze};
+
+pub enum LabyrinthKind {
+    Unicursal,
+    Classical(u32)
+}
+
+pub struct Labyrinth {
+    columns: u32,
+    rows: u32,
+
+    // The cells along the path, from the entrance
+    path: Vec<(u32, u32)>,
+
+    // The goal at the end of a classical labyrinth's path.  The path of a
+    // unicursal labyrinth ends at an exit in the bottom wall instead.
+    goal: Option<(Range<u32>, Range<u32>)>
+}
+
+#[derive(PartialEq, Copy, Clone)]
+enum Side {
+    Left,
+    Right
+}
+
+// A turn from one circuit to another on one side of the axis
+struct Turn {
+    side: Side,
+    from: u32,
+    to: u32
+}
+
+impl Turn {
+    fn rows(&self) -> (u32, u32) {
+        (self.from.min(self.to), self.from.max(self.to))
+    }
+
+    fn encloses(&self, other: &Turn) -> bool {
+        let (low, high) = self.rows();
+        let (other_low, other_high) = other.rows();
+        self.side == other.side && low < other_low && other_high < high
+    }
+}
+
+impl Labyrinth {
+    // A unicursal labyrinth filling a grid of about the given size.
+    pub fn unicursal(columns: u32, rows: u32) -> Labyrinth {
+        let mut maze = Maze::new((columns / 2).max(2), (rows / 2).max(2), 1);
+        maze.generate();
+        Labyrinth::around(&maze)
+    }
+
+    // The unicursal labyrinth that runs around the walls of a maze.
+    fn around(maze: &Maze) -> Labyrinth {
+        let columns = 2 * maze.x_size;
+        let rows = 2 * maze.y_size;
+
+        // Follow the loop from the lower-left lane, leaving along the west
+        // wall of the start cell, until it comes back beside where it began.
+        let mut path: Vec<(u32, u32)> = vec![(0, 0)];
+        let mut previous = (0, 0);
+        let mut current = (0, 1);
+        while current != (1, 0) {
+            path.push(current);
+            let next = Direction::ALL.iter()
+                .filter_map(|direction| step_lane(maze, current, direction))
+                .find(|next| *next != previous)
+                .unwrap();
+            previous = current;
+            current = next;
+        }
+        path.push(current);
+
+        Labyrinth {
+            columns,
+            rows,
+            path,
+            goal: None
+        }
+    }
+
+    // A classical labyrinth of 7, 11 or 15 circuits, with about the given
+    // ratio of height to width.
+    pub fn classical(circuits: u32, aspect: f64) -> Labyrinth {
+        // Circuit 0 is the outermost.  The path enters on the middle circuit
+        // and works outwards, crosses to the circuit beyond the middle, then
+        // jumps to the innermost and works back out to it.
+        let middle = (circuits - 1) / 2;
+        let mut order: Vec<u32> = (0..middle).rev().collect();
+        order.push(middle);
+        order.extend((middle + 1..circuits).rev());
+
+        // Each circuit is entered at one end and left at the other, so the
+        // side of each turn alternates.  The entrance is on the left.
+        let mut turns: Vec<Turn> = Vec::new();
+        let mut side = Side::Right;
+        for pair in order.windows(2) {
+            turns.push(Turn { side, from: pair[0], to: pair[1] });
+            side = if side == Side::Left { Side::Right } else { Side::Left };
+        }
+        let goal_side = side;
+
+        // How many turns enclose each turn, and the distance from the axis
+        // of the ends of each circuit.  A circuit that meets the axis itself,
+        // for the entrance or the goal, ends right beside it.
+        let depth: Vec<u32> = turns.iter()
+            .map(|turn| turns.iter().filter(|other| other.encloses(turn)).count() as u32)
+            .collect();
+        let end_distance = |circuit: u32, side: Side| {
+            turns.iter().zip(depth.iter())
+                .find(|(turn, _)| turn.side == side && (turn.from == circuit || turn.to == circuit))
+                .map(|(_, depth)| depth + 2)
+                .unwrap_or(1)
+        };
+
+        let axis = (0..circuits)
+            .map(|circuit| circuit + end_distance(circuit, Side::Left).max(end_distance(circuit, Side::Right)))
+            .max()
+            .unwrap();
+        let columns = 2 * axis + 1;
+        let rows = (2 * circuits + 1).max((columns as f64 * aspect).round() as u32);
+
+        let end = |circuit: u32, side: Side| match side {
+            Side::Left => axis - end_distance(circuit, side),
+            Side::Right => axis + end_distance(circuit, side)
+        };
+
+        // The entrance runs up the axis to the first circuit.
+        let mut path: Vec<(u32, u32)> = (0..=order[0]).map(|row| (axis, row)).collect();
+        let mut entered = Side::Left;
+        for (index, circuit) in order.iter().enumerate() {
+            path.extend(ring(*circuit, columns, rows, end(*circuit, Side::Left), end(*circuit, Side::Right), entered));
+
+            if let Some(turn) = turns.get(index) {
+                let column = match turn.side {
+                    Side::Left => axis - 1 - depth[index],
+                    Side::Right => axis + 1 + depth[index]
+                };
+                if turn.from < turn.to {
+                    path.extend((turn.from..=turn.to).map(|row| (column, row)));
+                } else {
+                    path.extend((turn.to..=turn.from).rev().map(|row| (column, row)));
+                }
+                entered = turn.side;
+            }
+        }
+
+        // From the last circuit, up the axis into the goal.
+        let last = *order.last().unwrap();
+        debug_assert!(goal_side != entered);
+        path.extend((last..=circuits).map(|row| (axis, row)));
+
+        Labyrinth {
+            columns,
+            rows,
+            path,
+            goal: Some((circuits..columns - circuits, circuits..rows - circuits))
+        }
+    }
+
+    // Number of cells along the path.
+    pub fn path_length(&self) -> usize {
+        self.path.len()
+    }
+
+    pub fn to_pdf(&self, doc: &Document, solution: bool, filename: &str) {
+        let width_pt = Pt(doc.page_width_in * 72.0);
+        let height_pt = Pt(doc.page_height_in * 72.0);
+        let (pdf, page1, layer1) = PdfDocument::new("Labyrinth", Mm::from(width_pt), Mm::from(height_pt), "Solution");
+
+        if solution {
+            let solution_layer = pdf.get_page(page1).get_layer(layer1);
+            self.draw_solution(&solution_layer, doc);
+        }
+
+        let main_layer = pdf.get_page(page1).add_layer("Puzzle");
+        self.draw(&main_layer, doc);
+
+        pdf.save(&mut BufWriter::new(File::create(filename).unwrap())).unwrap();
+    }
+
+    fn draw(&self, layer: &PdfLayerReference, doc: &Document) {
+        layer.set_fill_color(Color::Cmyk(Cmyk::new(1.0, 1.0, 1.0, 1.0, None)));
+        layer.set_outline_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+        layer.set_outline_thickness(doc.line_width_pt);
+        layer.set_line_cap_style(LineCapStyle::ProjectingSquare);
+        layer.set_line_join_style(LineJoinStyle::Miter);
+
+        let (columns, rows) = (self.columns as i32, self.rows as i32);
+        let position = self.path_positions();
+        let open = |a: (i32, i32), b: (i32, i32)| self.is_open(&position, a, b);
+
+        // Runs of wall along each grid line, horizontal then vertical
+        for y in 0..=rows {
+            let walls: Vec<bool> = (0..columns).map(|x| !open((x, y - 1), (x, y))).collect();
+            for run in runs(&walls) {
+                let points = [(run.start as f64, y as f64), (run.end as f64, y as f64)];
+                layer.add_shape(get_wall_line(&points, false, columns, rows, doc));
+            }
+        }
+        for x in 0..=columns {
+            let walls: Vec<bool> = (0..rows).map(|y| !open((x - 1, y), (x, y))).collect();
+            for run in runs(&walls) {
+                let points = [(x as f64, run.start as f64), (x as f64, run.end as f64)];
+                layer.add_shape(get_wall_line(&points, false, columns, rows, doc));
+            }
+        }
+
+        // Cells the path does not use are filled in solid.
+        for y in 0..self.rows {
+            for x in 0..self.columns {
+                if position[(y * self.columns + x) as usize].is_none() && !self.in_goal(x, y) {
+                    layer.add_shape(get_rectangle(x, y, x + 1, y + 1, self.columns, self.rows, doc));
+                }
+            }
+        }
+
+        if let Some((x_range, y_range)) = &self.goal {
+            let size = (x_range.end - x_range.start).min(y_range.end - y_range.start);
+            let x = x_range.start + (x_range.end - x_range.start - size) / 2;
+            let y = y_range.start + (y_range.end - y_range.start - size) / 2;
+            layer.add_shape(get_star_icon(size, size, x, y, self.columns, self.rows, doc));
+        }
+    }
+
+    fn draw_solution(&self, layer: &PdfLayerReference, doc: &Document) {
+        layer.set_fill_color(Color::Cmyk(Cmyk::new(0.0, 0.0, 1.0, 0.0, None)));
+        layer.set_outline_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+        layer.set_outline_thickness(doc.line_width_pt);
+
+        for step in self.path.windows(2) {
+            let x0 = step[0].0.min(step[1].0);
+            let y0 = step[0].1.min(step[1].1);
+            let x1 = step[0].0.max(step[1].0) + 1;
+            let y1 = step[0].1.max(step[1].1) + 1;
+            layer.add_shape(get_rectangle(x0, y0, x1, y1, self.columns, self.rows, doc));
+        }
+
+        if let Some((x_range, y_range)) = &self.goal {
+            layer.add_shape(get_rectangle(x_range.start, y_range.start, x_range.end, y_range.end, self.columns, self.rows, doc));
+        }
+    }
+
+    // The position along the path of each cell, if it is on the path.
+    fn path_positions(&self) -> Vec<Option<usize>> {
+        let mut position: Vec<Option<usize>> = vec![None; (self.columns * self.rows) as usize];
+        for (index, (x, y)) in self.path.iter().enumerate() {
+            position[(y * self.columns + x) as usize] = Some(index);
+        }
+        position
+    }
+
+    fn in_goal(&self, x: u32, y: u32) -> bool {
+        match &self.goal {
+            Some((x_range, y_range)) => x_range.contains(&x) && y_range.contains(&y),
+            None => false
+        }
+    }
+
+    // True if there is no wall between two neighbouring cells, either of
+    // which may be outside the grid.  Consecutive cells of the path are
+    // open to each other, as are the cells of the goal, and the ends of the
+    // path on the bottom row are open to the outside.
+    fn is_open(&self, position: &[Option<usize>], a: (i32, i32), b: (i32, i32)) -> bool {
+        let inside = |(x, y): (i32, i32)| x >= 0 && y >= 0 && x < self.columns as i32 && y < self.rows as i32;
+        let at = |(x, y): (i32, i32)| position[(y as u32 * self.columns + x as u32) as usize];
+
+        match (inside(a), inside(b)) {
+            (true, true) => {
+                match (at(a), at(b)) {
+                    (Some(i), Some(j)) if i.abs_diff(j) == 1 => true,
+                    _ => self.in_goal(a.0 as u32, a.1 as u32) && self.in_goal(b.0 as u32, b.1 as u32)
+                }
+            },
+            (false, true) | (true, false) => {
+                let (outside, cell) = if inside(a) { (b, a) } else { (a, b) };
+                let last = if self.goal.is_none() { Some(self.path.len() - 1) } else { None };
+                outside.1 == -1 && (at(cell) == Some(0) || at(cell).is_some() && at(cell) == last)
+            },
+            (false, false) => true
+        }
+    }
+}
+
+// The lane reached by moving from a lane of a unicursal labyrinth in the given
+// direction, if the two are joined.  Lane (column, row) is one quarter of the
+// maze cell (column / 2, row / 2).  The quarters of a cell are joined around
+// the middle, except where a passage leaves the cell: the wall down its middle
+// separates them.  Quarters of neighbouring cells are joined where there is a
+// passage between the cells.
+fn step_lane(maze: &Maze, (column, row): (u32, u32), direction: &Direction) -> Option<(u32, u32)> {
+    let (next_column, next_row) = match direction {
+        Direction::North => (column, row + 1),
+        Direction::East => (column + 1, row),
+        Direction::South => (column, row.checked_sub(1)?),
+        Direction::West => (column.checked_sub(1)?, row)
+    };
+    if next_column >= 2 * maze.x_size || next_row >= 2 * maze.y_size {
+        return None;
+    }
+
+    // Work from the lower or left lane of the two
+    let (lower_column, lower_row) = (column.min(next_column), row.min(next_row));
+    let cell = maze.get_cell(lower_column / 2, lower_row / 2);
+    let passage = |direction: Direction| maze.get_passage(&cell, &direction).is_some();
+
+    let joined = if next_row == row {
+        if lower_column % 2 == 0 {
+            !passage(if row % 2 == 0 { Direction::South } else { Direction::North })
+        } else {
+            passage(Direction::East)
+        }
+    } else if lower_row % 2 == 0 {
+        !passage(if column % 2 == 0 { Direction::West } else { Direction::East })
+    } else {
+        passage(Direction::North)
+    };
+
+    if joined {
+        Some((next_column, next_row))
+    } else {
+        None
+    }
+}
+
+// The cells of a circuit, starting from its end on the given side, going
+// away from the axis and around to its end on the other side.
+fn ring(circuit: u32, columns: u32, rows: u32, left_end: u32, right_end: u32, from: Side) -> Vec<(u32, u32)> {
+    let (left, right) = (circuit, columns - 1 - circuit);
+    let (bottom, top) = (circuit, rows - 1 - circuit);
+
+    let mut cells: Vec<(u32, u32)> = Vec::new();
+    cells.extend((left..=left_end).rev().map(|x| (x, bottom)));
+    cells.extend((bottom + 1..=top).map(|y| (left, y)));
+    cells.extend((left + 1..=right).map(|x| (x, top)));
+    cells.extend((bottom..top).rev().map(|y| (right, y)));
+    cells.extend((right_end..right).rev().map(|x| (x, bottom)));
+
+    if from == Side::Right {
+        cells.reverse();
+    }
+    cells
+}
+
+// The ranges of consecutive true values.
+fn runs(values: &[bool]) -> Vec<Range<i32>> {
+    let mut runs: Vec<Range<i32>> = Vec::new();
+    let mut start: Option<i32> = None;
+    for (index, value) in values.iter().chain([false].iter()).enumerate() {
+        match (start, value) {
+            (None, true) => start = Some(index as i32),
+            (Some(first), false) => {
+                runs.push(first..index as i32);
+                start = None;
+            },
+            _ => ()
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Labyrinth;
+    use crate::Maze;
+    use std::collections::HashSet;
+
+    // No cell is on the path twice, each step is to a neighbouring cell,
+    // and the path starts on the bottom row.
+    fn assert_single_path(labyrinth: &Labyrinth) {
+        let path = &labyrinth.path;
+        assert_eq!(path.iter().collect::<HashSet<_>>().len(), path.len());
+        for step in path.windows(2) {
+            assert_eq!(step[0].0.abs_diff(step[1].0) + step[0].1.abs_diff(step[1].1), 1);
+        }
+        assert_eq!(path[0].1, 0);
+    }
+
+    #[test]
+    fn unicursal_path_follows_the_walls_of_the_maze() {
+        // A 3x2 maze: along the bottom and up into the finish, with a
+        // branch from the start to the middle of the top row.
+        let maze = Maze::carve(3, 2, 1, &[&[(0, 0), (1, 0), (2, 0), (2, 1)], &[(0, 0), (0, 1), (1, 1)]]);
+        let labyrinth = Labyrinth::around(&maze);
+
+        assert_eq!((labyrinth.columns, labyrinth.rows), (6, 4));
+        assert_single_path(&labyrinth);
+        assert_eq!(labyrinth.path_length(), 24);
+        assert_eq!(labyrinth.path[..4].to_vec(), vec![(0, 0), (0, 1), (0, 2), (0, 3)]);
+        assert_eq!(*labyrinth.path.last().unwrap(), (1, 0));
+
+        // The dead end at (1, 1) of the maze is a U-turn across its top.
+        let at = |cell: (u32, u32)| labyrinth.path.iter().position(|lane| *lane == cell).unwrap();
+        assert_eq!(at((3, 3)), at((2, 3)) + 1);
+    }
+
+    #[test]
+    fn classical_path_takes_the_cretan_order_of_circuits() {
+        let labyrinth = Labyrinth::classical(7, 1.0);
+        let (columns, rows) = (labyrinth.columns, labyrinth.rows);
+        assert_single_path(&labyrinth);
+        let (x, y) = *labyrinth.path.last().unwrap();
+        assert!(labyrinth.in_goal(x, y));
+
+        // Only the circuits pass through the band along the top.
+        let mut order: Vec<u32> = labyrinth.path.iter()
+            .filter(|(_, y)| *y >= rows - 7)
+            .map(|(x, y)| (*x).min(columns - 1 - x).min(rows - 1 - y))
+            .collect();
+        order.dedup();
+        assert_eq!(order, vec![2, 1, 0, 3, 6, 5, 4]);
+    }
+}
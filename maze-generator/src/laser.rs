@@ -0,0 +1,217 @@
+// Laser cutter output.  The walls are given a real thickness and written as
+// closed outlines in DXF or SVG, ready to cut.  The walls form one piece,
+// and since the passages all join up they leave a single hole in it, so the
+// piece is cut as its outside edge and one outline inside.  It is glued onto
+// an optional base plate cut beside it.  Outlines are offset by half the
+// kerf, so the parts come out at their intended size after the laser burns
+// away the kerf.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::Maze;
+use crate::walls::{get_outlines, WallBlocks};
+
+// Gap between the wall piece and the base plate
+const PART_SPACING_MM: f64 = 10.0;
+
+pub enum LaserFormat {
+    Dxf,
+    Svg
+}
+
+pub struct LaserSettings {
+    pub format: LaserFormat,
+    pub cell_mm: f64,
+    pub wall_thickness_mm: f64,
+    pub kerf_mm: f64,
+    pub base_plate: bool
+}
+
+impl LaserSettings {
+    pub fn filename(&self) -> &'static str {
+        match self.format {
+            LaserFormat::Dxf => "maze.dxf",
+            LaserFormat::Svg => "maze.svg"
+        }
+    }
+}
+
+// A closed outline in millimetres.  Material is on the left when following
+// the points in order, so outer edges run counter-clockwise and holes run
+// clockwise.
+type Outline = Vec<(f64, f64)>;
+
+impl Maze {
+    // Write the cut file.  Returns the number of outlines written.
+    pub fn to_laser(&self, settings: &LaserSettings, filename: &str) -> usize {
+        let blocks = self.wall_blocks();
+        let width_mm = WallBlocks::edge(blocks.columns, settings.cell_mm, settings.wall_thickness_mm);
+        let height_mm = WallBlocks::edge(blocks.rows, settings.cell_mm, settings.wall_thickness_mm);
+
+        let mut walls: Vec<Outline> = get_outlines(&blocks).iter()
+            .map(|outline| outline.iter()
+                .map(|(column, row)| (
+                    WallBlocks::edge(*column, settings.cell_mm, settings.wall_thickness_mm),
+                    WallBlocks::edge(*row, settings.cell_mm, settings.wall_thickness_mm)))
+                .collect())
+            .collect();
+
+        // The base plate matches the outside of the walls, and sits to the
+        // right of them on the sheet.
+        let mut base: Vec<Outline> = Vec::new();
+        if settings.base_plate {
+            let x0 = width_mm + PART_SPACING_MM;
+            let x1 = x0 + width_mm;
+            base.push(vec![(x0, 0.0), (x1, 0.0), (x1, height_mm), (x0, height_mm)]);
+        }
+
+        for outline in walls.iter_mut().chain(base.iter_mut()) {
+            *outline = offset_outline(outline, settings.kerf_mm / 2.0);
+        }
+
+        // Leave room for the kerf offset around the parts.
+        let margin_mm = settings.kerf_mm;
+        let sheet_width_mm = if settings.base_plate { 2.0 * width_mm + PART_SPACING_MM } else { width_mm } + 2.0 * margin_mm;
+        let sheet_height_mm = height_mm + 2.0 * margin_mm;
+        for outline in walls.iter_mut().chain(base.iter_mut()) {
+            for point in outline.iter_mut() {
+                point.0 += margin_mm;
+                point.1 += margin_mm;
+            }
+        }
+
+        let mut out = BufWriter::new(File::create(filename).unwrap());
+        match settings.format {
+            LaserFormat::Dxf => write_dxf(&mut out, &walls, &base),
+            LaserFormat::Svg => write_svg(&mut out, &walls, &base, sheet_width_mm, sheet_height_mm)
+        }.unwrap();
+
+        walls.len() + base.len()
+    }
+}
+
+// Move every side of a rectilinear outline outward, away from the material,
+// by the given distance.  Each corner moves along both of its sides' normals.
+fn offset_outline(outline: &[(f64, f64)], distance: f64) -> Outline {
+    let count = outline.len();
+    let outward = |from: (f64, f64), to: (f64, f64)| {
+        // Material is on the left, so outward is the right-hand normal.
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        (dy / length, -dx / length)
+    };
+
+    (0..count)
+        .map(|i| {
+            let previous = outline[(i + count - 1) % count];
+            let point = outline[i];
+            let next = outline[(i + 1) % count];
+            let incoming = outward(previous, point);
+            let outgoing = outward(point, next);
+            (point.0 + distance * (incoming.0 + outgoing.0), point.1 + distance * (incoming.1 + outgoing.1))
+        })
+        .collect()
+}
+
+// AutoCAD R12 DXF, which almost every laser cutter program can import.
+// Walls and base plate are on separate layers.  R12 has no header variable
+// for the drawing units, so the millimetres have to be chosen on import.
+fn write_dxf<W: Write>(out: &mut W, walls: &[Outline], base: &[Outline]) -> std::io::Result<()> {
+    writeln!(out, "0\nSECTION\n2\nENTITIES")?;
+    for (layer, outlines) in [("WALLS", walls), ("BASE", base)].iter() {
+        for outline in outlines.iter() {
+            writeln!(out, "0\nPOLYLINE\n8\n{}\n66\n1\n10\n0.0\n20\n0.0\n30\n0.0\n70\n1", layer)?;
+            for (x, y) in outline {
+                writeln!(out, "0\nVERTEX\n8\n{}\n10\n{:.4}\n20\n{:.4}\n30\n0.0", layer, x, y)?;
+            }
+            writeln!(out, "0\nSEQEND\n8\n{}", layer)?;
+        }
+    }
+    writeln!(out, "0\nENDSEC\n0\nEOF")?;
+
+    Ok(())
+}
+
+// SVG in millimetres with hairline strokes.  Walls are red and the base plate
+// blue, so they can be given separate cut settings or order.
+fn write_svg<W: Write>(out: &mut W, walls: &[Outline], base: &[Outline], width_mm: f64, height_mm: f64) -> std::io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.3}mm\" height=\"{:.3}mm\" viewBox=\"0 0 {:.3} {:.3}\">",
+        width_mm, height_mm, width_mm, height_mm)?;
+
+    for (id, color, outlines) in [("walls", "#ff0000", walls), ("base", "#0000ff", base)].iter() {
+        writeln!(out, "  <g id=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"0.01\">", id, color)?;
+        for outline in outlines.iter() {
+            // SVG y runs down the page.
+            let points: Vec<String> = outline.iter()
+                .map(|(x, y)| format!("{:.4},{:.4}", x, height_mm - y))
+                .collect();
+            writeln!(out, "    <polygon points=\"{}\"/>", points.join(" "))?;
+        }
+        writeln!(out, "  </g>")?;
+    }
+    writeln!(out, "</svg>")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{offset_outline, LaserFormat, LaserSettings};
+    use crate::Maze;
+    use std::{fs, process};
+
+    #[test]
+    fn kerf_offset_grows_parts_and_shrinks_holes() {
+        let part = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        assert_eq!(offset_outline(&part, 0.5), vec![(-0.5, -0.5), (10.5, -0.5), (10.5, 10.5), (-0.5, 10.5)]);
+
+        let hole = [(0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0)];
+        assert_eq!(offset_outline(&hole, 0.5), vec![(0.5, 0.5), (0.5, 9.5), (9.5, 9.5), (9.5, 0.5)]);
+    }
+
+    #[test]
+    fn dxf_has_the_wall_piece_and_base_plate_on_their_layers() {
+        // A corridor three cells long: the walls are one piece with one hole.
+        let maze = Maze::carve(3, 1, 1, &[&[(0, 0), (1, 0), (2, 0)]]);
+        let settings = LaserSettings {
+            format: LaserFormat::Dxf,
+            cell_mm: 10.0,
+            wall_thickness_mm: 2.0,
+            kerf_mm: 0.2,
+            base_plate: true
+        };
+        let path = std::env::temp_dir().join(format!("maze-laser-{}.dxf", process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(maze.to_laser(&settings, path), 3);
+        let dxf = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert!(dxf.starts_with("0\nSECTION\n2\nENTITIES\n"));
+        assert!(dxf.ends_with("0\nENDSEC\n0\nEOF\n"));
+
+        // Group codes and values alternate, one per line.
+        let lines: Vec<&str> = dxf.lines().collect();
+        let pairs: Vec<(&str, &str)> = lines.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+        let polylines: Vec<&str> = pairs.windows(2)
+            .filter(|window| window[0] == ("0", "POLYLINE"))
+            .map(|window| window[1].1)
+            .collect();
+        assert_eq!(polylines, vec!["WALLS", "WALLS", "BASE"]);
+
+        // The walls are 3 cells and a wall thick across, and 1 cell and a
+        // wall high, grown by half the kerf, with the kerf as a margin.
+        let vertices: Vec<(&str, f64, f64)> = pairs.iter().enumerate()
+            .filter(|(_, pair)| **pair == ("0", "VERTEX"))
+            .map(|(i, _)| (pairs[i + 1].1, pairs[i + 2].1.parse().unwrap(), pairs[i + 3].1.parse().unwrap()))
+            .collect();
+        let extent = |layer: &str, coordinate: fn(&(&str, f64, f64)) -> f64| {
+            let values: Vec<f64> = vertices.iter().filter(|vertex| vertex.0 == layer).map(coordinate).collect();
+            (values.iter().cloned().fold(f64::MAX, f64::min), values.iter().cloned().fold(f64::MIN, f64::max))
+        };
+        assert_eq!(extent("WALLS", |vertex| vertex.1), (0.1, 32.3));
+        assert_eq!(extent("WALLS", |vertex| vertex.2), (0.1, 12.3));
+        assert_eq!(extent("BASE", |vertex| vertex.1), (42.1, 74.3));
+    }
+}
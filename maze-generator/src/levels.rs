@@ -0,0 +1,319 @@
+// Multi-level mazes.  The levels are floors of the same size, stacked one
+// above the other in the cell grid: level k is rows k * level_height up to
+// (k + 1) * level_height, and get_adjacent() never crosses from one level to
+// the next.  Instead, stairs join a cell to the cell directly above or below
+// it on the next level.  A stair is stored as an edge in Maze::stairs, in the
+// entry of the cell it was climbed from, so the stairs are part of the same
+// tree as the ordinary edges.
+//
+// Each level is drawn on its own page of the PDF, with an upward triangle in
+// cells that have stairs up and a downward one in cells with stairs down.
+
+use printpdf::*;
+use rand::prelude::*;
+
+use crate::content::StreamedContent;
+use crate::{caption, transform_x, transform_y, Cell, Document, Maze};
+
+const POINTS_PER_INCH: f64 = 72.0;
+
+// Chance that a tip at a dead end climbs to a level that has already been
+// reached.  The first tip to reach a dead end below or above a level that has
+// not been reached always climbs, so every level is generated.
+const STAIRS_CHANCE: f64 = 0.02;
+
+// Stair flags stored for each cell
+const STAIRS_UP: u8 = 1;
+const STAIRS_DOWN: u8 = 1 << 1;
+
+impl Maze {
+    // The level that row y belongs to, counting up from 0.
+    pub fn level(&self, y: u32) -> u32 {
+        y / self.level_height
+    }
+
+    // Advance a tip at a dead end by climbing stairs to the level above or
+    // below, if the cell there has not been visited yet.  Stairs to a new
+    // level are always taken, and others only occasionally.  Returns the cell
+    // the stairs lead to.
+    pub fn climb(&mut self, current: &Cell, dead_end: bool) -> Option<Cell> {
+        if self.levels == 1 || !dead_end || current.start_area() || current.finish_area() {
+            return None;
+        }
+
+        let mut options: Vec<(u8, Cell)> = Vec::new();
+        if let Some(above) = self.get_above(current) {
+            options.push((STAIRS_UP, above));
+        }
+        if let Some(below) = self.get_below(current) {
+            options.push((STAIRS_DOWN, below));
+        }
+        options.retain(|(_, next)| !next.visited() && !next.start_area() && !next.finish_area());
+
+        let new_levels: Vec<(u8, Cell)> = options.iter()
+            .filter(|(_, next)| !self.entered[self.level(next.y) as usize])
+            .copied()
+            .collect();
+        if !new_levels.is_empty() {
+            options = new_levels;
+        } else if self.rng.gen::<f64>() >= STAIRS_CHANCE {
+            return None;
+        }
+
+        let (stairs, next) = *options.choose(&mut self.rng)?;
+        let level = self.level(next.y) as usize;
+        self.entered[level] = true;
+        let index = self.index(current.x, current.y);
+        self.stairs[index] |= stairs;
+        self.mark_as_visited(&next);
+
+        Some(next)
+    }
+
+    // The cell that a visited cell was reached from by stairs, if any.
+    pub fn get_stairs_previous(&self, cell: &Cell) -> Option<Cell> {
+        if self.levels == 1 {
+            return None;
+        }
+        if let Some(below) = self.get_below(cell) {
+            if self.stairs[self.index(below.x, below.y)] & STAIRS_UP != 0 {
+                return Some(below);
+            }
+        }
+        if let Some(above) = self.get_above(cell) {
+            if self.stairs[self.index(above.x, above.y)] & STAIRS_DOWN != 0 {
+                return Some(above);
+            }
+        }
+        None
+    }
+
+    // True if stairs join the cell to the one above it.
+    fn has_stairs_up(&self, cell: &Cell) -> bool {
+        match self.get_above(cell) {
+            Some(above) => self.stairs[self.index(cell.x, cell.y)] & STAIRS_UP != 0 ||
+                           self.stairs[self.index(above.x, above.y)] & STAIRS_DOWN != 0,
+            None => false
+        }
+    }
+
+    // True if stairs join the cell to the one below it.
+    fn has_stairs_down(&self, cell: &Cell) -> bool {
+        match self.get_below(cell) {
+            Some(below) => self.stairs[self.index(cell.x, cell.y)] & STAIRS_DOWN != 0 ||
+                           self.stairs[self.index(below.x, below.y)] & STAIRS_UP != 0,
+            None => false
+        }
+    }
+
+    // The cells reached by climbing any stairs in the given cell.
+    pub fn get_stairs_moves(&self, cell: &Cell) -> Vec<Cell> {
+        let mut moves: Vec<Cell> = Vec::new();
+        if self.has_stairs_up(cell) {
+            moves.push(self.get_above(cell).unwrap());
+        }
+        if self.has_stairs_down(cell) {
+            moves.push(self.get_below(cell).unwrap());
+        }
+        moves
+    }
+
+    pub fn has_stairs(&self, cell: &Cell) -> bool {
+        self.has_stairs_up(cell) || self.has_stairs_down(cell)
+    }
+
+    fn get_above(&self, cell: &Cell) -> Option<Cell> {
+        if self.level(cell.y) + 1 < self.levels {
+            Some(self.get_cell(cell.x, cell.y + self.level_height))
+        } else {
+            None
+        }
+    }
+
+    fn get_below(&self, cell: &Cell) -> Option<Cell> {
+        if self.level(cell.y) > 0 {
+            Some(self.get_cell(cell.x, cell.y - self.level_height))
+        } else {
+            None
+        }
+    }
+
+    // Write a PDF with one page per level, the lowest level first.
+    pub fn to_levels_pdf(&self, doc: &Document, solution: bool, filename: &str) {
+        let width = Mm::from(Pt(doc.page_width_in * POINTS_PER_INCH));
+        let height = Mm::from(Pt(doc.page_height_in * POINTS_PER_INCH));
+        let (pdf, page1, layer1) = PdfDocument::new(doc.caption.document_title(), width, height, "Solution");
+        let font = pdf.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+        let bold = pdf.add_builtin_font(BuiltinFont::HelveticaBold).unwrap();
+
+        // The levels are drawn as one tall document, shifted down by the
+        // height of the levels below so that each lands on its own page.
+        let printable_height_in = doc.page_height_in - 2.0 * doc.margin_in;
+        let stack_doc = Document {
+            page_height_in: doc.page_height_in + (self.levels - 1) as f64 * printable_height_in,
+            ..doc.clone()
+        };
+
+        let mut walls = StreamedContent::new();
+        for level in 0..self.levels {
+            let (page, layer) = if level == 0 {
+                (page1, layer1)
+            } else {
+                pdf.add_page(width, height, "Solution")
+            };
+            let shift_y = -(level as f64) * printable_height_in;
+
+            let solution_layer = pdf.get_page(page).get_layer(layer);
+            begin_level(&solution_layer, doc, shift_y);
+            self.draw_background(&solution_layer, &stack_doc);
+            self.draw_heat_map(&solution_layer, &stack_doc);
+            if solution {
+                self.draw_solution(&solution_layer, &font, &stack_doc);
+            }
+            solution_layer.restore_graphics_state();
+
+            let main_layer = pdf.get_page(page).add_layer("Puzzle");
+            begin_level(&main_layer, doc, shift_y);
+            let first_row = (level * self.level_height) as i32;
+            self.draw_maze_region(&main_layer, &stack_doc, &mut walls,
+                -1..self.x_size as i32 + 1, first_row - 1..first_row + self.level_height as i32 + 1);
+            for icon in self.get_stairs_icons(level, &stack_doc) {
+                main_layer.add_shape(icon);
+            }
+            self.draw_puzzle(&main_layer, &bold, &stack_doc);
+            main_layer.restore_graphics_state();
+
+            self.draw_wrap_labels(&main_layer, &font, doc, level * self.level_height..(level + 1) * self.level_height);
+            if solution && level == 0 {
+                self.draw_answer_key(&main_layer, &bold, doc);
+            }
+
+            let label = format!("Level {} of {}.  Triangles mark stairs up and down to the same spot on the next level.",
+                level + 1, self.levels);
+            main_layer.use_text(
+                label, 8.0,
+                Mm::from(Pt(doc.margin_in * POINTS_PER_INCH)),
+                Mm::from(Pt(doc.margin_in / 3.0 * POINTS_PER_INCH)),
+                &font);
+        }
+
+        caption::save_pdf(pdf, doc, walls, filename);
+    }
+
+    // A triangle pointing up in the upper half of each cell with stairs up,
+    // and one pointing down in the lower half of each cell with stairs down.
+    fn get_stairs_icons(&self, level: u32, doc: &Document) -> Vec<Line> {
+        let mut icons: Vec<Line> = Vec::new();
+
+        for y in level * self.level_height..(level + 1) * self.level_height {
+            for x in 0..self.x_size {
+                let cell = self.get_cell(x, y);
+                let (x0, y0) = (x as f64, y as f64);
+
+                if self.has_stairs_up(&cell) {
+                    icons.push(get_triangle(
+                        &[(x0 + 0.2, y0 + 0.55), (x0 + 0.8, y0 + 0.55), (x0 + 0.5, y0 + 0.9)],
+                        self.x_size, self.y_size, doc));
+                }
+                if self.has_stairs_down(&cell) {
+                    icons.push(get_triangle(
+                        &[(x0 + 0.2, y0 + 0.45), (x0 + 0.8, y0 + 0.45), (x0 + 0.5, y0 + 0.1)],
+                        self.x_size, self.y_size, doc));
+                }
+            }
+        }
+
+        icons
+    }
+}
+
+fn get_triangle(points: &[(f64, f64)], x_size: u32, y_size: u32, doc: &Document) -> Line {
+    // Nudged by the line width offset, like the walls, to sit centered in
+    // the space between them.
+    let offset = Pt(doc.line_width_pt / 2.0);
+
+    Line {
+        points: points.iter()
+            .map(|(x, y)| (Point::new(
+                Mm::from(transform_x(*x, x_size as i32, doc) + offset),
+                Mm::from(transform_y(*y, y_size as i32, doc) + offset)), false))
+            .collect(),
+        is_closed: true,
+        has_fill: true,
+        has_stroke: false,
+        is_clipping_path: false,
+    }
+}
+
+// Clip drawing to the printable area of the page, leaving room for the
+// stroke of the top and right walls, and shift the stack of levels down by
+// shift_y inches.  Must be paired with restore_graphics_state().
+fn begin_level(layer: &PdfLayerReference, doc: &Document, shift_y: f64) {
+    let stroke_in = doc.line_width_pt / POINTS_PER_INCH;
+
+    layer.save_graphics_state();
+
+    let x0 = Mm::from(Pt(doc.margin_in * POINTS_PER_INCH));
+    let y0 = Mm::from(Pt(doc.margin_in * POINTS_PER_INCH));
+    let x1 = Mm::from(Pt((doc.page_width_in - doc.margin_in + stroke_in) * POINTS_PER_INCH));
+    let y1 = Mm::from(Pt((doc.page_height_in - doc.margin_in + stroke_in) * POINTS_PER_INCH));
+    layer.add_shape(Line {
+        points: vec![
+            (Point::new(x0, y0), false),
+            (Point::new(x1, y0), false),
+            (Point::new(x1, y1), false),
+            (Point::new(x0, y1), false)],
+        is_closed: true,
+        has_fill: false,
+        has_stroke: false,
+        is_clipping_path: true,
+    });
+
+    layer.set_ctm(CurTransMat::Translate(Mm(0.0), Mm::from(Pt(shift_y * POINTS_PER_INCH))));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Direction, Maze};
+
+    // Two 3x2 floors.  The lower one snakes from the start to a dead end
+    // at (0, 1), under the upper floor's (0, 3).
+    fn two_floors() -> Maze {
+        let mut maze = Maze::with_levels(3, 2, 2, 1);
+        maze.mark_as_visited(&maze.get_cell(0, 0));
+        maze.carve_paths(&[&[(0, 0), (1, 0), (2, 0), (2, 1), (1, 1), (0, 1)]]);
+        maze
+    }
+
+    #[test]
+    fn dead_end_climbs_to_a_new_level() {
+        let mut maze = two_floors();
+        assert!(maze.get_adjacent(&maze.get_cell(0, 1), &Direction::North).is_none());
+        assert!(maze.climb(&maze.get_cell(0, 1), false).is_none());
+
+        let next = maze.climb(&maze.get_cell(0, 1), true).unwrap();
+        assert_eq!((next.x, next.y), (0, 3));
+        assert_eq!(maze.entered, vec![true, true]);
+
+        let moves = |x: u32, y: u32| -> Vec<(u32, u32)> {
+            maze.get_moves(&maze.get_cell(x, y)).iter().map(|cell| (cell.x, cell.y)).collect()
+        };
+        assert_eq!(moves(0, 1), vec![(1, 1), (0, 3)]);
+        assert_eq!(moves(0, 3), vec![(0, 1)]);
+        let previous = maze.get_stairs_previous(&maze.get_cell(0, 3)).unwrap();
+        assert_eq!((previous.x, previous.y), (0, 1));
+
+        // The solution goes up the stairs and across the upper floor.
+        maze.carve_paths(&[&[(0, 3), (0, 2), (1, 2), (2, 2), (2, 3)]]);
+        assert_eq!(maze.solution_path(), vec![(0, 0), (1, 0), (2, 0), (2, 1), (1, 1), (0, 1),
+            (0, 3), (0, 2), (1, 2), (2, 2), (2, 3)]);
+    }
+
+    #[test]
+    fn no_stairs_from_the_start() {
+        let mut maze = Maze::with_levels(3, 2, 2, 1);
+        maze.mark_as_visited(&maze.get_cell(0, 0));
+        assert!(maze.climb(&maze.get_cell(0, 0), true).is_none());
+        assert_eq!(maze.entered, vec![true, false]);
+    }
+}
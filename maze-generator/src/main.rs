@@ -3,50 +3,134 @@
 // Author: Jake Kugel
 
 use rand::prelude::*;
-use rand::distributions::WeightedIndex;
 use std::cmp::PartialEq;
 use printpdf::*;
-use std::fs::File;
-use std::io::BufWriter;
 use num_traits::pow;
 use std::time::Instant;
 use std::io::{self, Write};
 use std::process;
 use std::cmp;
-
-// Represents a single cell in rectangular grid.  The 'edges' field contains
-// array of booleans indicating a valid path starting at this cell and going
-// to adjacent cell.
+use std::ops::Range;
+
+mod animation;
+#[cfg(feature = "baseline")]
+mod baseline;
+mod benchmark;
+mod caption;
+mod checkpoints;
+mod content;
+mod difficulty;
+mod dungeon;
+mod graph;
+mod growth;
+mod heatmap;
+mod keys;
+mod labyrinth;
+mod laser;
+mod levels;
+mod play;
+mod plotter;
+mod poster;
+mod race;
+mod regenerate;
+mod settings;
+mod stl;
+mod symmetry;
+mod theme;
+mod topology;
+mod update;
+mod walls;
+mod weave;
+
+use animation::{AnimationFormat, AnimationSettings};
+use caption::Caption;
+use checkpoints::Checkpoints;
+use content::StreamedContent;
+use difficulty::{Difficulty, Tuning};
+use dungeon::Room;
+use graph::GraphFormat;
+use growth::{Growth, Schedule};
+use heatmap::{HeatMap, Palette};
+use keys::Puzzle;
+use labyrinth::{Labyrinth, LabyrinthKind};
+use laser::{LaserFormat, LaserSettings};
+use plotter::PlotterFormat;
+use poster::Poster;
+use race::Race;
+use settings::Settings;
+use stl::StlSettings;
+use symmetry::Symmetry;
+use theme::{SolutionStyle, Theme};
+use topology::Topology;
+use walls::Polyline;
+
+// Flags stored for each cell.  The low four bits are the edges: a bit is set
+// when there is a valid path starting at this cell and going to the adjacent
+// cell in that direction (see Direction::bit()).
+const VISITED: u8 = 1 << 4;
+const START_AREA: u8 = 1 << 5;
+const FINISH_AREA: u8 = 1 << 6;
+
+// Set once a path has backtracked out of a cell.  A cell with no unvisited
+// neighbours never gains one, so any other path that reaches a dead end here
+// would only follow the same path back, and is dropped instead.
+const EXHAUSTED: u8 = 1 << 7;
+
+// Number of mazes generated in search of a fair goal for a race
+const RACE_ATTEMPTS: u32 = 50;
+
+// Represents a single cell in rectangular grid.  The maze only stores the
+// flags and cell type of each cell; a Cell is a copy of those values along
+// with the cell's position, built on demand by Maze::get_cell().
 #[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone, Debug)]
 struct Cell {
     cell_type: u8,
     x: u32,
     y: u32,
-    edges: [bool; 4],
-    visited: bool,
-    start_area: bool,
-    finish_area: bool
+    flags: u8,
+    under: u8
 }
 
 impl Cell {
-    fn draw_edge(&mut self, direction: &Direction) {
-        self.edges[direction.to_usize()] = true;
+    fn has_edge(&self, direction: &Direction) -> bool {
+        self.flags & direction.bit() != 0
     }
 
-    fn mark_as_visited(&mut self) {
-        self.visited = true;
+    fn visited(&self) -> bool {
+        self.flags & VISITED != 0
     }
 
-    fn has_edge(&self, direction: &Direction) -> bool {
-        return self.edges[direction.to_usize()];
+    fn start_area(&self) -> bool {
+        self.flags & START_AREA != 0
+    }
+
+    fn finish_area(&self) -> bool {
+        self.flags & FINISH_AREA != 0
+    }
+
+    // True if a passage tunnels under this cell in the given direction.
+    fn tunnels(&self, direction: &Direction) -> bool {
+        self.under & direction.bit() != 0
     }
 }
 
+#[derive(Clone)]
 struct Document {
     page_height_in: f64,
     page_width_in: f64,
     line_width_pt: f64,
-    margin_in: f64
+    margin_in: f64,
+
+    // Colors and line styles of the PDF (see theme.rs)
+    theme: Theme,
+
+    // For block walls, which fill whole squares of the grid, the width of a
+    // passage over the width of a wall.  None for walls drawn as lines.
+    blocks: Option<f64>,
+
+    // The title printed with the maze, if any, and the settings recorded in
+    // the PDF's document information (see caption.rs)
+    caption: Caption
 }
 
 // Represents one of North, South, East, West
@@ -73,6 +157,9 @@ impl std::fmt::Display for Direction {
 }
 
 impl Direction {
+    // The four directions, clockwise from north
+    const ALL: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+
     fn right(&self) -> Direction {
         match self {
             Direction::North => Direction::East,
@@ -100,12 +187,13 @@ impl Direction {
         }
     }
 
-    fn to_usize(&self) -> usize {
+    // The edge flag for this direction
+    fn bit(self) -> u8 {
         match self {
-            Direction::North => 0,
-            Direction::East => 1,
-            Direction::South => 2,
-            Direction::West => 3
+            Direction::North => 1,
+            Direction::East => 1 << 1,
+            Direction::South => 1 << 2,
+            Direction::West => 1 << 3
         }
     }
 }
@@ -123,6 +211,7 @@ struct MeanderStrategy {
 }
 
 impl MeanderStrategy {
+    #[allow(clippy::needless_return)]
     fn new(i: u32, j: u32, k: u32, l: u32, m: u32, n: u32) -> MeanderStrategy {
         return MeanderStrategy {
             weight_north_south: i,
@@ -136,43 +225,53 @@ impl MeanderStrategy {
 
     // Get next direction based on meander strategy.  Direction is weighted
     // random choice based on struct weights.
+    #[allow(clippy::clone_on_copy)]
     fn get_direction(&self, maze: &mut Maze, current: &Cell) -> Option<Direction> {
+        // A tip at the start has come from nowhere, so pick a heading at
+        // random rather than always favouring North.
         let previous_direction = match maze.get_previous_direction(current) {
             Some(direction) => direction,
-            None => Direction::North
+            None => *Direction::ALL.choose(&mut maze.rng).unwrap()
         };
 
         let mut direction = previous_direction.clone();
 
-        // Build a vector of valid directions and corresponding vector of weights
-        let mut directions = Vec::<Direction>::new();
-        let mut weights = Vec::<u32>::new();
+        // Build an array of valid directions and corresponding array of
+        // weights.  Fixed size arrays avoid allocating on every step.
+        let mut directions = [Direction::North; 4];
+        let mut weights = [0u32; 4];
+        let mut count = 0;
         for _ in 0..4 {
             direction = direction.right();
-            if maze.is_valid(current, &direction) {
-                directions.push(direction.clone());
-                weights.push(self.get_weight(maze, current, &direction, &previous_direction));
+            if let Some(next) = maze.get_adjacent(current, &direction) {
+                if maze.is_valid(&next) {
+                    directions[count] = direction;
+                    weights[count] = self.get_weight(current, &next, &direction, &previous_direction);
+                    count += 1;
+                }
             }
         }
 
         // Make a weighted random choice
-        if directions.len() > 0 {
-            let dist = WeightedIndex::new(&weights).unwrap();
-
+        if count > 0 {
+            let total: u32 = weights[..count].iter().sum();
+            let mut choice = maze.rng.gen_range(0, total);
 
-            return Some(directions.get(dist.sample(&mut maze.rng)).unwrap().clone());
-        } else {
-            None
+            for (direction, weight) in directions[..count].iter().zip(weights[..count].iter()) {
+                if choice < *weight {
+                    return Some(*direction);
+                }
+                choice -= weight;
+            }
         }
+
+        None
     }
 
-    fn get_weight(&self, maze: &Maze, current: &Cell, direction: &Direction, previous_direction: &Direction) -> u32 {
+    #[allow(clippy::assign_op_pattern, clippy::needless_return)]
+    fn get_weight(&self, current: &Cell, next: &Cell, direction: &Direction, previous_direction: &Direction) -> u32 {
         let mut weight: u32 = 0;
 
-        // Because we called maze.is_valid() before get_weight(), we know
-        // get_adjacent() will return a cell, and we can unwrap immediately.
-        let next: Cell = maze.get_adjacent(current, direction).unwrap();
-
         if *direction == Direction::North || *direction == Direction::South {
             weight = weight + self.weight_north_south;
         } else {
@@ -200,10 +299,51 @@ impl MeanderStrategy {
 }
 
 // Represents a two-dimensional array of cells, also list of available strategies.
-// The grid is a square of width and height equal to 'size'.  The lower-left
-// corner is x=0, y=0, and the top-right corner is x=size-1, y=size-1.
+// The grid is x_size cells wide and y_size cells high.  The lower-left
+// corner is x=0, y=0, and the top-right corner is x=x_size-1, y=y_size-1.
+// Cells are stored row by row in flat vectors (see Maze::index()).
 struct Maze {
-    cells: Vec<Vec<Cell>>,
+    flags: Vec<u8>,
+    cell_types: Vec<u8>,
+
+    // For each cell of a weave maze, the direction bits of a passage that
+    // tunnels under it (see weave.rs).
+    under: Vec<u8>,
+    weave: bool,
+
+    // For a multi-level maze, the stairs climbed from each cell, the number
+    // of levels stacked in the grid, each level_height rows high, and which
+    // levels generate() has reached so far (see levels.rs).
+    stairs: Vec<u8>,
+    levels: u32,
+    level_height: u32,
+    entered: Vec<bool>,
+
+    // How the edges of each level join up (see topology.rs).
+    topology: Topology,
+
+    // Locked doors and their keys, if any (see keys.rs).
+    puzzle: Option<Puzzle>,
+
+    // Coins to collect on the way to the finish, if any (see checkpoints.rs).
+    checkpoints: Option<Checkpoints>,
+
+    // Rooms of a dungeon, and the number of the room each cell is in,
+    // counting from 1, or 0 outside the rooms (see dungeon.rs).
+    rooms: Vec<Room>,
+    room_cells: Vec<u16>,
+    room_labels: bool,
+
+    // The shared goal of a two-player race, if any (see race.rs).
+    race: Option<Race>,
+
+    // Whether only half the maze is generated and mirrored (see symmetry.rs).
+    symmetry: Symmetry,
+
+    // Colors for the cells by their distance from the start, if any (see
+    // heatmap.rs).
+    heat_map: Option<HeatMap>,
+
     strategies: Vec<MeanderStrategy>,
     x_size: u32,
     y_size: u32,
@@ -213,22 +353,42 @@ struct Maze {
     finish_y: u32,
     start_x: u32,
     start_y: u32,
-    rng: rand::rngs::ThreadRng
+    rng: StdRng,
+
+    // The seed the random number generator started from (see
+    // difficulty.rs).
+    seed: u64,
+
+    // How the tips grow: their split chance, how many there may be, and the
+    // order they move in (see growth.rs).
+    growth: Growth,
+
+    // When recording, every edge drawn by generate(), in order.
+    history: Option<Vec<(u32, u32, Direction)>>
 }
 
 impl Maze {
     fn new(x_size: u32, y_size: u32, start_finish_size: u32) -> Maze {
+        Maze::with_levels(x_size, y_size, 1, start_finish_size)
+    }
 
-        // Construct a column-major data structure so we can reference using
-        // familiar [x][y] notation.
-        let mut cells: Vec<Vec<Cell>> = Vec::new();
-        for x in 0..x_size {
-            let mut column_vec : Vec<Cell> = Vec::new();
-            for y in 0..y_size {
+    // A maze of the given number of levels, each x_size by level_height
+    // cells.  The start area is on the lowest level and the finish area on
+    // the highest.
+    #[allow(clippy::needless_bool, clippy::redundant_field_names, clippy::needless_return)]
+    fn with_levels(x_size: u32, level_height: u32, levels: u32, start_finish_size: u32) -> Maze {
+
+        let y_size = level_height * levels;
+        let cell_count = x_size as usize * y_size as usize;
+        let mut flags: Vec<u8> = Vec::with_capacity(cell_count);
+        let mut cell_types: Vec<u8> = Vec::with_capacity(cell_count);
+        for y in 0..y_size {
+            for x in 0..x_size {
 
                 // cell_type - different cell types have different meander strategies
+                let level_y = y % level_height;
                 let cell_type = if pow((x as f64 - (x_size as f64 / 2.0)) / x_size as f64, 2)
-                                   + pow((y as f64 - (y_size as f64 / 2.0)) / x_size as f64, 2) < 0.15 {
+                                   + pow((level_y as f64 - (level_height as f64 / 2.0)) / x_size as f64, 2) < 0.15 {
                     0
                 } else {
                     1
@@ -248,25 +408,24 @@ impl Maze {
                     false
                 };
 
-                let cell: Cell = Cell {
-                    cell_type: cell_type,
-                    edges: [false, false, false, false],
-                    x: x,
-                    y: y,
-                    visited: false,
-                    start_area: start_area,
-                    finish_area: finish_area
-                };
+                let mut cell_flags: u8 = 0;
+                if start_area {
+                    cell_flags |= START_AREA;
+                }
+                if finish_area {
+                    cell_flags |= FINISH_AREA;
+                }
 
-                column_vec.push(cell);
+                flags.push(cell_flags);
+                cell_types.push(cell_type);
             }
-            cells.push(column_vec);
         }
 
+        let seed: u64 = thread_rng().gen();
         let mut strategies: Vec<MeanderStrategy> = Vec::new();
 
         // Inside the circle, paths are generally long, straight east-west
-        let strategy1 = MeanderStrategy::new(1, 100, 1, 1, 1, 1);
+        let strategy1 = MeanderStrategy::new(1, difficulty::CORRIDOR_WEIGHT, 1, 1, 1, 1);
 
         // Outside the circle, paths move more randomly
         let strategy2 = MeanderStrategy::new(1, 1, 1, 1, 1, 1);
@@ -275,7 +434,23 @@ impl Maze {
         strategies.push(strategy2);
 
         let maze: Maze = Maze {
-            cells: cells,
+            flags,
+            cell_types,
+            under: vec![0; cell_count],
+            weave: false,
+            stairs: vec![0; cell_count],
+            levels,
+            level_height,
+            entered: (0..levels).map(|level| level == 0).collect(),
+            topology: Topology::Plane,
+            puzzle: None,
+            checkpoints: None,
+            rooms: Vec::new(),
+            room_cells: vec![0; cell_count],
+            room_labels: false,
+            race: None,
+            symmetry: Symmetry::None,
+            heat_map: None,
             strategies: strategies,
             x_size: x_size,
             y_size: y_size,
@@ -285,7 +460,10 @@ impl Maze {
             start_y: start_finish_size - 1,
             finish_x: 0,
             finish_y: 0,
-            rng: thread_rng()
+            rng: StdRng::seed_from_u64(seed),
+            seed,
+            growth: Growth::new(),
+            history: None
         };
 
         return maze;
@@ -298,86 +476,133 @@ impl Maze {
 
     // Generate a directed, acyclic graph with the maze starting cell as the
     // root, and that visits every other cell in the square grid of cells.
+    #[allow(clippy::len_zero, clippy::assign_op_pattern)]
     fn generate(&mut self) {
         let mut paths: Vec<Cell> = Vec::new();
+        self.block_image_half();
 
         // Start with a single path
         paths.push(self.get_cell(self.start_x, self.start_y));
 
+        // Advance (or back-track) the tips in the order of the schedule (see
+        // growth.rs).
+        let mut index = 0;
         while paths.len() > 0 {
+            index = self.next_tip(&paths, index);
+            let path = paths[index];
 
-            // Loop through the paths list and advance (or back-track) each.
-            let mut index = 0;
-            while index < paths.len() {
-                let path = paths[index];
-
-                let direction: Option<Direction> = self.meander(&path);
-                match direction {
-                    Some(direction) => {
-                        // Five percent of the time, a path that is not at
-                        // a dead end will split into two paths.
-                        if self.rng.gen::<f64>() < 0.05 as f64 {
-                            paths.push(self.get_cell(path.x, path.y));
-                        }
+            let direction: Option<Direction> = self.meander(&path);
 
-                        let next = self.get_adjacent(&path, &direction).unwrap();
-
-                        // Special handling if the next cell is in the finish area.
-                        if next.finish_area {
-                            self.draw_edge(&path, &direction);
-                            self.mark_as_visited(&next);
-                            self.finish_x = next.x;
-                            self.finish_y = next.y;
-                            self.goal_reached = true;
-                        } else {
-                            self.draw_edge(&path, &direction);
-                            self.mark_as_visited(&next);
-                            paths[index] = self.get_cell(next.x, next.y);
-                        }
+            // In a weave maze, the tip may tunnel under a neighbouring
+            // corridor instead.
+            if let Some(next) = self.tunnel(&path, direction.is_none()) {
+                paths[index] = next;
+                index = index + 1;
+                continue;
+            }
 
-                        index = index + 1;
+            // In a multi-level maze, a tip at a dead end may climb to
+            // another level.
+            if let Some(next) = self.climb(&path, direction.is_none()) {
+                paths[index] = next;
+                index = index + 1;
+                continue;
+            }
 
+            match direction {
+                Some(direction) => {
+                    // Some of the time, five percent by default, a path
+                    // that is not at a dead end will split into two
+                    // paths.
+                    if self.rng.gen::<f64>() < self.growth.split_chance {
+                        self.add_tips(&mut paths, Some(path));
                     }
-                    // No direction recommended - this tip is at dead end - need to back track
-                    None => {
-                        if path.x == self.start_x && path.y == self.start_y {
-                            // If a path backtracks all the way to the beginning, remove
-                            // it from paths vector.
-                            paths.remove(index);
-                        } else {
-                            paths[index] = self.get_previous_cell(&path);
-                            index = index + 1;
+
+                    let next = self.get_adjacent(&path, &direction).unwrap();
+
+                    // Special handling if the next cell is in the finish area.
+                    if next.finish_area() {
+                        self.draw_edge(&path, &direction);
+                        self.mark_as_visited(&next);
+                        self.finish_x = next.x;
+                        self.finish_y = next.y;
+                        self.goal_reached = true;
+                    } else {
+                        self.draw_edge(&path, &direction);
+                        self.mark_as_visited(&next);
+                        paths[index] = next;
+
+                        // Entering a dungeon room opens all of it.
+                        if !self.rooms.is_empty() {
+                            let exits = self.open_room(&next);
+                            self.add_tips(&mut paths, exits);
                         }
                     }
+
+                    index = index + 1;
+
+                }
+                // No direction recommended - this tip is at dead end - need to back track
+                None => {
+                    let cell_index = self.index(path.x, path.y);
+
+                    if path.x == self.start_x && path.y == self.start_y ||
+                       self.flags[cell_index] & EXHAUSTED != 0 {
+                        // If a path backtracks all the way to the beginning, or
+                        // another path has already backtracked out of this cell,
+                        // remove it from paths vector.  In round robin order the
+                        // last path takes its place and is advanced next, so every
+                        // path still moves once per pass without shifting the rest
+                        // of the vector.
+                        self.remove_tip(&mut paths, index);
+                    } else {
+                        self.flags[cell_index] |= EXHAUSTED;
+                        paths[index] = self.get_previous_cell(&path);
+                        index = index + 1;
+                    }
                 }
             }
         }
+
+        self.copy_to_image_half();
     }
 
     fn draw_edge(&mut self, current: &Cell, direction: &Direction) {
-        let current: &mut Cell = self.cells.get_mut(current.x as usize).unwrap().get_mut(current.y as usize).unwrap();
-        current.draw_edge(direction);
+        let index = self.index(current.x, current.y);
+        self.flags[index] |= direction.bit();
+
+        if let Some(history) = &mut self.history {
+            history.push((current.x, current.y, *direction));
+        }
     }
 
     fn mark_as_visited(&mut self, current: &Cell) {
-        let current: &mut Cell = self.cells.get_mut(current.x as usize).unwrap().get_mut(current.y as usize).unwrap();
-        current.mark_as_visited();
+        let index = self.index(current.x, current.y);
+        self.flags[index] |= VISITED;
     }
 
     // Given a cell that has been visited already, return the direction
     // of the graph edge that arrives here.
+    #[inline(always)]
     fn get_previous_direction(&self, cell: &Cell) -> Option<Direction> {
         let mut direction = Direction::North;
 
         // Loop through North, East, South, West and check
         for _ in 0..4 {
-            match self.get_adjacent(cell, &direction) {
-                Some(adjacent) => {
-                    if adjacent.has_edge(&direction.opposite()) {
-                        return Some(direction.opposite());
-                    }
-                },
-                None => {}
+            // A passage tunnelling under the cell does not lead into it.
+            // Without weaving there are no tunnels to follow, which keeps
+            // this cheap on every step of generation.
+            let adjacent = if !self.weave {
+                self.get_adjacent(cell, &direction)
+            } else if cell.tunnels(&direction) {
+                None
+            } else {
+                self.get_linked(cell, &direction)
+            };
+            if let Some(adjacent) = adjacent {
+                if adjacent.has_edge(&direction.opposite()) {
+                    return Some(direction.opposite());
+                }
             }
 
             direction = direction.right();
@@ -387,167 +612,298 @@ impl Maze {
 
     // Given a cell that has been visited already, follow the edge that
     // arrived here and return the previous cell.
+    #[allow(clippy::needless_borrow)]
     fn get_previous_cell(&self, cell: &Cell) -> Cell {
+        if let Some(previous) = self.get_stairs_previous(cell) {
+            return previous;
+        }
+
         let backward: &Direction = &self
             .get_previous_direction(&cell)
             .unwrap()
             .opposite();
-        let previous = self.get_adjacent(&cell, backward).unwrap();
+        let previous = self.get_linked(cell, backward).unwrap();
 
         self.get_cell(previous.x, previous.y)
     }
 
+    // The cells along the solution, from the start cell to the first cell
+    // reached in the finish area.
+    fn solution_path(&self) -> Vec<(u32, u32)> {
+        let mut path: Vec<(u32, u32)> = vec![(self.finish_x, self.finish_y)];
+        let mut current = self.get_cell(self.finish_x, self.finish_y);
+
+        while ! (current.x == self.start_x && current.y == self.start_y) {
+            current = self.get_previous_cell(&current);
+            path.push((current.x, current.y));
+        }
+
+        path.reverse();
+        path
+    }
+
+    // The route drawn in the solution: the solver's route when there are
+    // doors to unlock, the tour when there are checkpoints to collect, and
+    // otherwise the solution itself.
+    fn solution_route(&self) -> Vec<(u32, u32)> {
+        self.puzzle_route()
+            .or_else(|| self.tour_route())
+            .unwrap_or_else(|| self.solution_path())
+    }
+
     // Given a cell and a direction, return the adjacent cell that is
     // arrived at by moving in the given direction.  If the direction would
-    // fall outside of the bounds of the maze, None is returned.
+    // fall outside of the bounds of the maze, or of the cell's level in a
+    // multi-level maze, the cell it wraps around to is returned, or None if
+    // the topology does not wrap that way.  With a single level, the level
+    // is the whole grid, so there is no need to divide to find its edges.
+    #[inline(always)]
     fn get_adjacent(&self, cell: &Cell, direction: &Direction) -> Option<Cell> {
         if *direction == Direction::North {
-            if cell.y == self.y_size - 1 {
-                None
+            if cell.y + 1 == self.y_size || self.levels > 1 && (cell.y + 1) % self.level_height == 0 {
+                self.wrap(cell, direction)
             } else {
                 Some(self.get_cell(cell.x, cell.y + 1))
             }
         } else if *direction == Direction::South {
-            if cell.y == 0 {
-                None
+            if cell.y == 0 || self.levels > 1 && cell.y % self.level_height == 0 {
+                self.wrap(cell, direction)
             } else {
                 Some(self.get_cell(cell.x, cell.y - 1))
             }
         } else if *direction == Direction::East {
             if cell.x == self.x_size - 1 {
-                None
+                self.wrap(cell, direction)
             } else {
                 Some(self.get_cell(cell.x + 1, cell.y))
             }
         } else { // west
             if cell.x == 0 {
-                None
+                self.wrap(cell, direction)
             } else {
                 Some(self.get_cell(cell.x - 1, cell.y))
             }
         }
     }
 
+    // Like get_adjacent(), but a passage that tunnels under the adjacent
+    // cell leads on to the cell beyond it.
+    fn get_linked(&self, cell: &Cell, direction: &Direction) -> Option<Cell> {
+        let adjacent = self.get_adjacent(cell, direction)?;
+        if adjacent.tunnels(direction) {
+            self.get_adjacent(&adjacent, direction)
+        } else {
+            Some(adjacent)
+        }
+    }
+
+    // The cell reached by moving from the given cell in the given direction,
+    // if there is a passage that way.  Cells within the start or finish area
+    // are always open to each other.
+    fn get_passage(&self, cell: &Cell, direction: &Direction) -> Option<Cell> {
+        // A passage under this cell cannot be reached from on top of it.
+        if cell.tunnels(direction) {
+            return None;
+        }
+
+        let next = self.get_linked(cell, direction)?;
+        if cell.has_edge(direction) || next.has_edge(&direction.opposite()) ||
+           (cell.start_area() && next.start_area()) ||
+           (cell.finish_area() && next.finish_area()) ||
+           self.same_room(cell, &next) {
+            Some(next)
+        } else {
+            None
+        }
+    }
+
+    // Every cell that can be moved to from the given cell, through its
+    // passages and up or down any stairs.
+    fn get_moves(&self, cell: &Cell) -> Vec<Cell> {
+        let mut moves: Vec<Cell> = Direction::ALL.iter()
+            .filter_map(|direction| self.get_passage(cell, direction))
+            .collect();
+        moves.extend(self.get_stairs_moves(cell));
+        moves
+    }
+
+    // Generation builds several cells for each step, so this and the other
+    // small lookups it goes through are always inlined.  The tunnels under
+    // a cell are only read in a weave maze.
+    #[inline(always)]
     fn get_cell(&self, x: u32, y: u32) -> Cell {
-        let cell_ref: &Cell = self.cells.get(x as usize).unwrap().get(y as usize).unwrap();
-        (*cell_ref).clone()
-    }
-
-    // Returns true if the cell arrived at by moving in direction from current
-    // cell is within maze bounds and hasn't been visited yet.
-    fn is_valid(&self, current: &Cell, direction: &Direction) -> bool {
-        match self.get_adjacent(current, direction) {
-            // If the target cell is not within maze boundary, is_valid returns false
-            None => false,
-            Some(target_cell) => {
-                if target_cell.visited || target_cell.start_area ||
-                    target_cell.finish_area && self.goal_reached {
-                        false
-                    } else {
-                        true
-                    }
-            }
+        let index = self.index(x, y);
+        Cell {
+            cell_type: self.cell_types[index],
+            x,
+            y,
+            flags: self.flags[index],
+            under: if self.weave { self.under[index] } else { 0 }
         }
     }
 
+    // Position of cell (x, y) within the flat flags and cell_types vectors.
+    #[inline(always)]
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.x_size as usize + x as usize
+    }
+
+    // The cell at the given position in the flat vectors.
+    fn get_cell_at(&self, index: usize) -> Cell {
+        self.get_cell((index % self.x_size as usize) as u32, (index / self.x_size as usize) as u32)
+    }
+
+    // Returns true if a path may move on into the target cell, a neighbour
+    // of its tip: it hasn't been visited yet, and is not in the start area
+    // or, once the goal is reached, the finish area.
+    #[allow(clippy::needless_bool)]
+    #[inline(always)]
+    fn is_valid(&self, target_cell: &Cell) -> bool {
+        if target_cell.visited() || target_cell.start_area() ||
+            target_cell.finish_area() && self.goal_reached {
+                false
+            } else {
+                true
+            }
+    }
+
     //https://docs.rs/printpdf/0.3.3/printpdf/types/pdf_layer/struct.PdfLayerReference.html#method.add_shape
     fn to_pdf(&self, doc: &Document, solution: bool, filename: &str) {
         let width_pt = Pt(doc.page_width_in * 72.0);
         let height_pt = Pt(doc.page_height_in * 72.0);
-        let (pdf, page1, layer1) = PdfDocument::new("Maze", Mm::from(width_pt), Mm::from(height_pt), "Solution");
+        let (pdf, page1, layer1) = PdfDocument::new(doc.caption.document_title(), Mm::from(width_pt), Mm::from(height_pt), "Solution");
+        let font = pdf.add_builtin_font(BuiltinFont::Helvetica).unwrap();
         let solution_layer = pdf.get_page(page1).get_layer(layer1);
 
+        self.draw_background(&solution_layer, doc);
+        self.draw_heat_map(&solution_layer, doc);
         if solution {
-            self.draw_solution(&solution_layer, doc);
+            self.draw_solution(&solution_layer, &font, doc);
         }
 
         let main_layer = pdf.get_page(page1).add_layer("Puzzle");
-        self.draw_maze(&main_layer, doc);
+        let mut walls = StreamedContent::new();
+        self.draw_maze(&main_layer, doc, &mut walls);
 
-        pdf.save(&mut BufWriter::new(File::create(filename).unwrap())).unwrap();
+        if self.topology != Topology::Plane {
+            self.draw_wrap_labels(&main_layer, &font, doc, 0..self.y_size);
+        }
+
+        self.draw_race(&main_layer, doc);
+
+        if self.room_labels {
+            let font = pdf.add_builtin_font(BuiltinFont::HelveticaBold).unwrap();
+            self.draw_room_labels(&main_layer, &font, doc);
+        }
+
+        if self.puzzle.is_some() {
+            let font = pdf.add_builtin_font(BuiltinFont::HelveticaBold).unwrap();
+            self.draw_puzzle(&main_layer, &font, doc);
+            if solution {
+                self.draw_answer_key(&main_layer, &font, doc);
+            }
+        }
+
+        if doc.caption.title.is_some() {
+            let bold = pdf.add_builtin_font(BuiltinFont::HelveticaBold).unwrap();
+            self.draw_caption(&main_layer, &font, &bold, doc);
+        }
+
+        caption::save_pdf(pdf, doc, walls, filename);
     }
 
-    fn draw_maze(&self, layer: &PdfLayerReference, doc: &Document) {
-        let fill_color = Color::Cmyk(Cmyk::new(1.0, 1.0, 1.0, 1.0, None));
-        let outline_color = Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None));
-        let mut dash_pattern = LineDashPattern::default();
-        dash_pattern.dash_1 = Some(20);
+    // Write the maze to a PDF of a single page, one page per level, or the
+    // sheets of a poster.
+    fn write_pdf(&self, doc: &Document, poster: Option<&Poster>, solution: bool, filename: &str) {
+        match poster {
+            Some(poster) => self.to_poster_pdf(doc, poster, solution, filename),
+            None if self.levels > 1 => self.to_levels_pdf(doc, solution, filename),
+            None => self.to_pdf(doc, solution, filename)
+        }
+    }
+
+    fn draw_maze(&self, layer: &PdfLayerReference, doc: &Document, walls: &mut StreamedContent) {
+        self.draw_maze_region(layer, doc, walls, -1..self.x_size as i32 + 1, -1..self.y_size as i32 + 1);
+    }
+
+    // Draw only the walls belonging to cells within the given ranges.  The
+    // ranges may extend one cell past the maze on each side to include the
+    // outer border.  The wall polylines, the bulk of a large maze, go in a
+    // stream of their own.
+    fn draw_maze_region(&self, layer: &PdfLayerReference, doc: &Document, walls: &mut StreamedContent, x_range: Range<i32>, y_range: Range<i32>) {
+        let fill_color = doc.theme.wall_color();
+        let outline_color = doc.theme.wall_color();
+
+        // Dashes a quarter of a cell long, so that a dashed wall still reads
+        // as a wall
+        let dash_length = (transform_x(0.25, self.x_size as i32, doc).0 - transform_x(0.0, self.x_size as i32, doc).0).round();
+        let dash_pattern = LineDashPattern { dash_1: Some((dash_length as i64).max(1)), ..LineDashPattern::default() };
 
         layer.set_fill_color(fill_color);
         layer.set_outline_color(outline_color);
         layer.set_outline_thickness(doc.line_width_pt); // In points
+        if doc.theme.dashed {
+            layer.set_line_dash_pattern(dash_pattern);
+        }
 
-        for x in -1..self.x_size as i32 + 1 {
-            for y in -1..self.y_size as i32 + 1 {
-
-                // Check if a horizontal line needed between cells (x, y) and (x, y + 1)
-                let horizontal_needed =
-                    if x == -1 || x == self.x_size as i32 { // left and right margin
-                        false
-                    } else if y == -1 || y == self.y_size as i32 - 1 { // top and bottom margin
-                        true
-                    } else if y == self.y_size as i32 {
-                        false
-                    } else {
-                        let cell: Cell = self.get_cell(x as u32, y as u32);
-                        let cell_above: Cell = self.get_cell(x as u32, y as u32 + 1);
-
-                        if (cell_above.edges[2] || cell.edges[0]) ||  // There is a path between the two cells
-                           (cell.start_area && cell_above.start_area) || // Two cells within start area
-                           (cell.finish_area && cell_above.finish_area) { // Two cells within finish area
-                            false
-                        } else {
-                            true
-                        }
-                    };
+        // Square caps and mitered corners, by default, fill the grid square
+        // where walls end or meet, see get_polyline().  Block walls are
+        // always square.
+        if doc.blocks.is_some() {
+            layer.set_line_cap_style(LineCapStyle::ProjectingSquare);
+            layer.set_line_join_style(LineJoinStyle::Miter);
+        } else {
+            layer.set_line_cap_style(doc.theme.caps);
+            layer.set_line_join_style(doc.theme.joins);
+        }
 
-                if horizontal_needed {
-                    let line: Line = get_line(x, y + 1, x + 1, y + 1, self.x_size as i32, self.y_size as i32, doc);
-                    layer.add_shape(line);
-                };
+        walls.begin(layer);
+        for polyline in self.wall_polylines(x_range.clone(), y_range.clone()) {
+            let line: Line = get_polyline(&polyline, self.x_size as i32, self.y_size as i32, doc);
+            walls.add_shape(&line);
+        }
 
-                // Check if a vertical line needed between cells (x, y) and (x + 1, y)
-                let vertical_needed =
-                    if y == -1 || y == self.y_size as i32 { // top and bottom margin
-                        false
-                    } else if x == -1 || x == self.x_size as i32 - 1 { // left and right margin
-                        true
-                    } else if x == self.x_size as i32 {
-                        false
-                    } else {
-                        let cell: Cell = self.get_cell(x as u32, y as u32);
-                        let cell_right: Cell = self.get_cell(x as u32 + 1, y as u32);
-
-                        if (cell_right.edges[3] || cell.edges[1]) || // There is a path between two cells
-                           (cell.start_area && cell_right.start_area) || // Two cells within start area
-                           (cell.finish_area && cell_right.finish_area) { // Two cells within finish area
-                            false
-                        } else {
-                            true
-                        }
-                    };
+        for points in self.crossing_walls(x_range, y_range) {
+            let line: Line = get_wall_line(&points, false, self.x_size as i32, self.y_size as i32, doc);
+            layer.add_shape(line);
+        }
 
-                if vertical_needed {
-                    let line: Line = get_line(x + 1, y, x + 1, y + 1, self.x_size as i32, self.y_size as i32, doc);
-                    layer.add_shape(line);
-                };
-            }
+        if doc.theme.dashed {
+            layer.set_line_dash_pattern(LineDashPattern::default());
+        }
+
+        for icon in self.get_icons(doc) {
+            layer.add_shape(icon);
+        }
+    }
+
+    fn get_icons(&self, doc: &Document) -> Vec<Line> {
+        // A race draws both homes in the players' colors instead.
+        if self.race.is_some() {
+            return Vec::new();
         }
 
         // Add house shape for starting area
         let home: Line = get_home_icon(self.start_finish_size, self.start_finish_size, 0, 0, self.x_size, self.y_size, doc);
-        layer.add_shape(home);
 
         // Add star shape for finish area
         let star: Line = get_star_icon(
             self.start_finish_size, self.start_finish_size,
             self.x_size - self.start_finish_size, self.y_size - self.start_finish_size,
             self.x_size, self.y_size, doc);
-        layer.add_shape(star);
 
+        let mut icons = vec![home, star];
+        icons.extend(self.get_checkpoint_icons(doc));
+        icons
     }
 
-    fn draw_solution(&self, layer: &PdfLayerReference, doc: &Document) {
+    // True if the route goes between two cells that are apart on the page,
+    // by stairs or a wrap-around passage.
+    fn is_jump(&self, from: (u32, u32), to: (u32, u32)) -> bool {
+        self.level(from.1) != self.level(to.1) || from.0.abs_diff(to.0) > 2 || from.1.abs_diff(to.1) > 2
+    }
+
+    fn draw_solution(&self, layer: &PdfLayerReference, font: &IndirectFontRef, doc: &Document) {
         let fill_color = Color::Cmyk(Cmyk::new(0.0, 0.0, 1.0, 0.0, None));
         let outline_color = Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None));
 
@@ -555,27 +911,33 @@ impl Maze {
         layer.set_outline_color(outline_color);
         layer.set_outline_thickness(doc.line_width_pt); // In points
 
-        let mut current_x = self.finish_x;
-        let mut current_y = self.finish_y;
+        if self.race.is_some() {
+            self.draw_race_solution(layer, font, doc);
+            return;
+        }
 
-        let mut previous = self.get_previous_cell(&self.get_cell(current_x, current_y));
+        if doc.theme.solution != SolutionStyle::Highlight {
+            self.draw_styled_solution(layer, font, doc);
+            return;
+        }
 
-        while ! (current_x == self.start_x && current_y == self.start_y) {
+        for step in self.solution_route().windows(2) {
+            // Stairs and wrap-around passages join cells that are apart on
+            // the page, so each end is highlighted on its own.
+            if self.is_jump(step[0], step[1]) {
+                for (x, y) in step.iter() {
+                    layer.add_shape(get_rectangle(*x, *y, x + 1, y + 1, self.x_size, self.y_size, doc));
+                }
+                continue;
+            }
 
-            let x0 = cmp::min(current_x, previous.x);
-            let y0 = cmp::min(current_y, previous.y);
-            let x1 = cmp::max(current_x, previous.x) + 1;
-            let y1 = cmp::max(current_y, previous.y) + 1;
+            let x0 = cmp::min(step[0].0, step[1].0);
+            let y0 = cmp::min(step[0].1, step[1].1);
+            let x1 = cmp::max(step[0].0, step[1].0) + 1;
+            let y1 = cmp::max(step[0].1, step[1].1) + 1;
 
             let rectangle = get_rectangle(x0, y0, x1, y1, self.x_size, self.y_size, doc);
             layer.add_shape(rectangle);
-
-            current_x = previous.x;
-            current_y = previous.y;
-
-            if ! (current_x == self.start_x && current_y == self.start_y) {
-                previous = self.get_previous_cell(&previous);
-            }
         }
 
         // Highlight the starting region
@@ -594,6 +956,44 @@ impl Maze {
     }
 }
 
+#[cfg(test)]
+impl Maze {
+    // A maze carved by hand, for tests.  Each path is a run of neighbouring
+    // cells starting from one that has been reached already, and the first
+    // passage into the finish area ends the solution there.
+    fn carve(x_size: u32, y_size: u32, start_finish_size: u32, paths: &[&[(u32, u32)]]) -> Maze {
+        let mut maze = Maze::new(x_size, y_size, start_finish_size);
+        maze.mark_as_visited(&maze.get_cell(maze.start_x, maze.start_y));
+        maze.carve_paths(paths);
+        maze
+    }
+
+    // Carve more paths into a maze, as in carve().  A step may be to any
+    // cell that get_adjacent() finds, so paths wrap around where the
+    // topology does.
+    fn carve_paths(&mut self, paths: &[&[(u32, u32)]]) {
+        for path in paths {
+            for step in path.windows(2) {
+                let cell = self.get_cell(step[0].0, step[0].1);
+                let (direction, next) = Direction::ALL.iter()
+                    .filter_map(|direction| Some((*direction, self.get_adjacent(&cell, direction)?)))
+                    .find(|(_, next)| (next.x, next.y) == step[1])
+                    .unwrap_or_else(|| panic!("{:?} and {:?} are not neighbours", step[0], step[1]));
+                assert!(cell.visited() && !next.visited(), "cannot carve from {:?} to {:?}", step[0], step[1]);
+
+                self.draw_edge(&cell, &direction);
+                self.mark_as_visited(&next);
+                if next.finish_area() && !self.goal_reached {
+                    self.finish_x = next.x;
+                    self.finish_y = next.y;
+                    self.goal_reached = true;
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::let_and_return)]
 fn get_rectangle(x1: u32, y1: u32, x2: u32, y2: u32, x_size: u32, y_size: u32, doc: &Document) -> Line {
     let offset = Pt(doc.line_width_pt);
 
@@ -620,35 +1020,33 @@ fn get_rectangle(x1: u32, y1: u32, x2: u32, y2: u32, x_size: u32, y_size: u32, d
     line
 }
 
-fn get_line(x1: i32, y1: i32, x2: i32, y2: i32, x_size: i32, y_size: i32, doc: &Document) -> Line {
-    let pdf_x1: Pt = transform_x(x1 as f64, x_size, doc);
-    let pdf_x2: Pt = transform_x(x2 as f64, x_size, doc);
-    let pdf_y1: Pt = transform_y(y1 as f64, y_size, doc);
-    let pdf_y2: Pt = transform_y(y2 as f64, y_size, doc);
+fn get_polyline(polyline: &Polyline, x_size: i32, y_size: i32, doc: &Document) -> Line {
+    let points: Vec<(f64, f64)> = polyline.points.iter()
+        .map(|(x, y)| (*x as f64, *y as f64))
+        .collect();
 
-    let horizontal = if x1 == x2 { false } else { true };
+    get_wall_line(&points, polyline.closed, x_size, y_size, doc)
+}
 
-    // The line endpoints need to be offset slightly to compensate for the
-    // line width.
-    let (x1_offset, x2_offset, y1_offset, y2_offset) = if horizontal {
-        (
-            Pt(0.0),
-            Pt(doc.line_width_pt),
-            Pt(doc.line_width_pt / 2.0),
-            Pt(doc.line_width_pt / 2.0))
-    } else {
-        (
-            Pt(doc.line_width_pt / 2.0),
-            Pt(doc.line_width_pt / 2.0),
-            Pt(0.0),
-            Pt(doc.line_width_pt))
-    };
+// A wall through points given in cell coordinates.
+fn get_wall_line(points: &[(f64, f64)], closed: bool, x_size: i32, y_size: i32, doc: &Document) -> Line {
+    // The points need to be offset slightly to compensate for the line
+    // width, so the wall's stroke sits above and to the right of the grid
+    // line.  Together with the projecting square line caps, this covers the
+    // same area as the individual wall segments drawn edge to edge.
+    let offset = Pt(doc.line_width_pt / 2.0);
+
+    let points: Vec<(Point, bool)> = points.iter()
+        .map(|(x, y)| {
+            let pdf_x: Pt = transform_x(*x, x_size, doc) + offset;
+            let pdf_y: Pt = transform_y(*y, y_size, doc) + offset;
+            (Point::new(Mm::from(pdf_x), Mm::from(pdf_y)), false)
+        })
+        .collect();
 
-    let endpoints = vec![(Point::new(Mm::from(pdf_x1 + x1_offset), Mm::from(pdf_y1 + y1_offset)), false),
-                       (Point::new(Mm::from(pdf_x2 + x2_offset), Mm::from(pdf_y2 + y2_offset)), false)];
     Line {
-        points: endpoints,
-        is_closed: false,
+        points,
+        is_closed: closed,
         has_fill: false,
         has_stroke: true,
         is_clipping_path: false,
@@ -695,6 +1093,7 @@ fn get_home_icon(cell_width: u32, cell_height: u32, cell_x: u32, cell_y: u32, x_
     }
 }
 
+#[allow(clippy::unnecessary_cast)]
 fn get_star_icon(cell_width: u32, cell_height: u32, cell_x: u32, cell_y: u32, x_size: u32, y_size: u32, doc: &Document) -> Line {
 
     // Radius of the points of the star
@@ -754,6 +1153,16 @@ fn transform_y(y: f64, y_size: i32, doc: &Document) -> Pt {
     Pt(((y / y_size as f64) * (doc.page_height_in - 2.0 * doc.margin_in) + doc.margin_in) * points_per_inch)
 }
 
+// The page margin, in inches, which is wider on large paper.
+fn page_margin(page_width_in: f64, page_height_in: f64) -> f64 {
+    if page_width_in > 11.0 && page_height_in > 17.0 {
+        1.0
+    } else {
+        0.5
+    }
+}
+
+#[allow(clippy::manual_range_contains)]
 fn get_user_input() -> (f64, f64, f64, f64) {
     let size = get_text_input("Enter maze cell size (micro, small, medium, large) [medium]: ");
     let (cell_size, line_width_pt) = match size.as_str() {
@@ -768,9 +1177,10 @@ fn get_user_input() -> (f64, f64, f64, f64) {
        }
     };
 
-    let page_size = get_text_input("Enter paper size (letter, custom) [letter]: ");
+    let page_size = get_text_input("Enter paper size (letter, a4, custom) [letter]: ");
     let (page_width_in, page_height_in) = match page_size.as_str() {
        "letter" => (8.5, 11.0),
+       "a4" => (8.27, 11.69),
        "custom" => {
             let page_width_in = get_float_input("Enter page width in inches (1..1000): ");
             if page_width_in < 1.0 || page_width_in > 1000.0 {
@@ -788,7 +1198,7 @@ fn get_user_input() -> (f64, f64, f64, f64) {
        },
        "" => (8.5, 11.0),
        _ => {
-         println!("Must enter 'letter' or 'custom'.");
+         println!("Must enter 'letter', 'a4', or 'custom'.");
          process::exit(1);
        }
     };
@@ -796,6 +1206,533 @@ fn get_user_input() -> (f64, f64, f64, f64) {
     (cell_size, line_width_pt, page_width_in, page_height_in)
 }
 
+// Where the finished maze is written.
+enum Output {
+    Pdf,
+    Plotter(PlotterFormat),
+    Laser(LaserSettings),
+    Stl(StlSettings),
+    Graph(GraphFormat),
+    Play { fog: bool },
+    Animation(AnimationSettings),
+    Labyrinth(LabyrinthKind)
+}
+
+fn get_output_input(cell_size: f64) -> Output {
+    let format = get_text_input("Enter output format (pdf, hpgl, gcode, dxf, svg, stl, dot, graphml, json, play, animation, labyrinth) [pdf]: ");
+    match format.as_str() {
+        "pdf" | "" => Output::Pdf,
+        "hpgl" => Output::Plotter(PlotterFormat::Hpgl),
+        "gcode" => Output::Plotter(PlotterFormat::Gcode),
+        "dxf" => Output::Laser(get_laser_input(LaserFormat::Dxf, cell_size)),
+        "svg" => Output::Laser(get_laser_input(LaserFormat::Svg, cell_size)),
+        "stl" => Output::Stl(get_stl_input(cell_size)),
+        "dot" => Output::Graph(GraphFormat::Dot),
+        "graphml" => Output::Graph(GraphFormat::GraphMl),
+        "json" => Output::Graph(GraphFormat::Json),
+        "play" => Output::Play { fog: get_fog_input() },
+        "animation" => Output::Animation(get_animation_input()),
+        "labyrinth" => Output::Labyrinth(get_labyrinth_input()),
+        _ => {
+            println!("Must enter 'pdf', 'hpgl', 'gcode', 'dxf', 'svg', 'stl', 'dot', 'graphml', 'json', 'play', 'animation', or 'labyrinth'.");
+            process::exit(1);
+        }
+    }
+}
+
+fn get_laser_input(format: LaserFormat, cell_size: f64) -> LaserSettings {
+    let cell_mm = cell_size * 25.4;
+
+    let wall_thickness_mm = get_float_input_or_default(
+        &format!("Enter wall thickness in mm [{:.1}]: ", cell_mm / 4.0), cell_mm / 4.0);
+    let kerf_mm = get_float_input_or_default("Enter laser kerf in mm [0.1]: ", 0.1);
+
+    // The passage between two walls must survive the kerf.
+    if wall_thickness_mm <= 0.0 || kerf_mm < 0.0 || wall_thickness_mm + kerf_mm >= cell_mm {
+        println!("Wall thickness plus kerf must be less than the cell size of {:.1} mm.", cell_mm);
+        process::exit(1);
+    }
+
+    let base_plate = get_text_input("Include base plate (yes, no) [yes]: ");
+    let base_plate = match base_plate.as_str() {
+        "yes" | "" => true,
+        "no" => false,
+        _ => {
+            println!("Must enter 'yes' or 'no'.");
+            process::exit(1);
+        }
+    };
+
+    LaserSettings {
+        format,
+        cell_mm,
+        wall_thickness_mm,
+        kerf_mm,
+        base_plate
+    }
+}
+
+fn get_stl_input(cell_size: f64) -> StlSettings {
+    let cell_mm = get_float_input_or_default(
+        &format!("Enter cell size in mm [{:.1}]: ", cell_size * 25.4), cell_size * 25.4);
+    let wall_thickness_mm = get_float_input_or_default(
+        &format!("Enter wall thickness in mm [{:.1}]: ", cell_mm / 4.0), cell_mm / 4.0);
+    let wall_height_mm = get_float_input_or_default(
+        &format!("Enter wall height in mm [{:.1}]: ", cell_mm / 2.0), cell_mm / 2.0);
+
+    if wall_thickness_mm <= 0.0 || wall_thickness_mm >= cell_mm {
+        println!("Wall thickness must be less than the cell size of {:.1} mm.", cell_mm);
+        process::exit(1);
+    }
+
+    if wall_height_mm <= 0.0 {
+        println!("Wall height must be greater than zero.");
+        process::exit(1);
+    }
+
+    StlSettings {
+        cell_mm,
+        wall_thickness_mm,
+        wall_height_mm
+    }
+}
+
+fn get_fog_input() -> bool {
+    let view = get_text_input("Enter view (fog, full) [fog]: ");
+    match view.as_str() {
+        "fog" | "" => true,
+        "full" => false,
+        _ => {
+            println!("Must enter 'fog' or 'full'.");
+            process::exit(1);
+        }
+    }
+}
+
+fn get_animation_input() -> AnimationSettings {
+    let format = get_text_input("Enter animation format (gif, svg, frames) [gif]: ");
+    let format = match format.as_str() {
+        "gif" | "" => AnimationFormat::Gif,
+        "svg" => AnimationFormat::Svg,
+        "frames" => AnimationFormat::Frames,
+        _ => {
+            println!("Must enter 'gif', 'svg', or 'frames'.");
+            process::exit(1);
+        }
+    };
+
+    let frames_per_second = get_float_input_or_default("Enter frames per second (1..100) [10]: ", 10.0);
+    if !(1.0..=100.0).contains(&frames_per_second) {
+        println!("Must enter value between 1 and 100.");
+        process::exit(1);
+    }
+
+    // Zero leaves the choice to the animation, which aims for 100 frames.
+    let steps_per_frame = get_float_input_or_default("Enter generation steps per frame (0 for automatic) [0]: ", 0.0);
+    if steps_per_frame < 0.0 {
+        println!("Must enter a value of 0 or more.");
+        process::exit(1);
+    }
+
+    AnimationSettings {
+        format,
+        frames_per_second,
+        steps_per_frame: if steps_per_frame >= 1.0 { Some(steps_per_frame as usize) } else { None }
+    }
+}
+
+fn get_labyrinth_input() -> LabyrinthKind {
+    let kind = get_text_input("Enter labyrinth type (unicursal, classical) [unicursal]: ");
+    match kind.as_str() {
+        "unicursal" | "" => LabyrinthKind::Unicursal,
+        "classical" => {
+            let circuits = get_float_input_or_default("Enter number of circuits (7, 11, 15) [7]: ", 7.0);
+            if circuits != 7.0 && circuits != 11.0 && circuits != 15.0 {
+                println!("Must enter 7, 11 or 15.");
+                process::exit(1);
+            }
+            LabyrinthKind::Classical(circuits as u32)
+        },
+        _ => {
+            println!("Must enter 'unicursal' or 'classical'.");
+            process::exit(1);
+        }
+    }
+}
+
+fn get_difficulty_input() -> Option<Difficulty> {
+    let level = get_text_input("Enter difficulty (any, easy, medium, hard) [any]: ");
+    match level.as_str() {
+        "any" | "" => None,
+        _ => match Difficulty::named(&level) {
+            Some(difficulty) => Some(difficulty),
+            None => {
+                println!("Must enter 'any', 'easy', 'medium', or 'hard'.");
+                process::exit(1);
+            }
+        }
+    }
+}
+
+// Returns the largest difference allowed between the players' routes, or
+// None for a maze without a race.
+fn get_race_input() -> Option<usize> {
+    let mode = get_text_input("Enter players (one, race) [one]: ");
+    match mode.as_str() {
+        "one" | "" => None,
+        "race" => {
+            let tolerance = get_float_input_or_default(
+                "Enter largest difference between the players' moves and decisions (0..20) [2]: ", 2.0);
+            if !(0.0..=20.0).contains(&tolerance) {
+                println!("Must enter value between 0 and 20.");
+                process::exit(1);
+            }
+            Some(tolerance as usize)
+        },
+        _ => {
+            println!("Must enter 'one' or 'race'.");
+            process::exit(1);
+        }
+    }
+}
+
+// Returns the number of dungeon rooms, and whether to number them.  Only
+// PDFs have room for labels.
+fn get_rooms_input(can_label: bool) -> (usize, bool) {
+    let rooms = get_float_input_or_default("Enter number of dungeon rooms (0..30) [0]: ", 0.0);
+    if !(0.0..=30.0).contains(&rooms) {
+        println!("Must enter value between 0 and 30.");
+        process::exit(1);
+    }
+    if rooms < 1.0 || !can_label {
+        return (rooms as usize, false);
+    }
+
+    let labels = get_text_input("Enter room labels (numbers, none) [numbers]: ");
+    match labels.as_str() {
+        "numbers" | "" => (rooms as usize, true),
+        "none" => (rooms as usize, false),
+        _ => {
+            println!("Must enter 'numbers' or 'none'.");
+            process::exit(1);
+        }
+    }
+}
+
+// Returns the colors of a heat map, or None for a plain maze.
+fn get_heat_map_input() -> Option<HeatMap> {
+    let coloring = get_text_input("Enter coloring (plain, heatmap) [plain]: ");
+    match coloring.as_str() {
+        "plain" | "" => return None,
+        "heatmap" => {},
+        _ => {
+            println!("Must enter 'plain' or 'heatmap'.");
+            process::exit(1);
+        }
+    }
+
+    let palette = get_text_input("Enter palette (rainbow, fire, ocean, gray) [rainbow]: ");
+    let palette = match palette.as_str() {
+        "" => Palette::Rainbow,
+        _ => match Palette::named(&palette) {
+            Some(palette) => palette,
+            None => {
+                println!("Must enter 'rainbow', 'fire', 'ocean', or 'gray'.");
+                process::exit(1);
+            }
+        }
+    };
+
+    let extent = get_text_input("Enter cells to color (all, solution) [all]: ");
+    let neighbourhood = match extent.as_str() {
+        "all" | "" => None,
+        "solution" => {
+            let moves = get_float_input_or_default("Enter moves away from the solution to color (0..20) [3]: ", 3.0);
+            if !(0.0..=20.0).contains(&moves) {
+                println!("Must enter value between 0 and 20.");
+                process::exit(1);
+            }
+            Some(moves as u32)
+        },
+        _ => {
+            println!("Must enter 'all' or 'solution'.");
+            process::exit(1);
+        }
+    };
+
+    Some(HeatMap { palette, neighbourhood })
+}
+
+// Returns the passage to wall width ratio for block walls, or None for walls
+// drawn as lines.
+fn get_blocks_input() -> Option<f64> {
+    let style = get_text_input("Enter wall style (lines, blocks) [lines]: ");
+    match style.as_str() {
+        "lines" | "" => None,
+        "blocks" => {
+            let ratio = get_float_input_or_default("Enter passage width over wall width (0.5..8) [1]: ", 1.0);
+            if !(0.5..=8.0).contains(&ratio) {
+                println!("Must enter value between 0.5 and 8.");
+                process::exit(1);
+            }
+            Some(ratio)
+        },
+        _ => {
+            println!("Must enter 'lines' or 'blocks'.");
+            process::exit(1);
+        }
+    }
+}
+
+// Returns the look of the PDF: one of the ready-made themes, or one built
+// from the answers to further prompts, and the style of the solution.
+fn get_theme_input() -> Theme {
+    let name = get_text_input("Enter theme (classic, blueprint, parchment, sketch, custom) [classic]: ");
+    let mut theme = match name.as_str() {
+        "" => Theme::new(),
+        "custom" => get_custom_theme_input(),
+        _ => match Theme::named(&name) {
+            Some(theme) => theme,
+            None => {
+                println!("Must enter 'classic', 'blueprint', 'parchment', 'sketch', or 'custom'.");
+                process::exit(1);
+            }
+        }
+    };
+
+    let style = get_text_input("Enter solution style (highlight, line, dotted, arrows, numbers) [highlight]: ");
+    theme.solution = match style.as_str() {
+        "" => SolutionStyle::Highlight,
+        _ => match SolutionStyle::named(&style) {
+            Some(style) => style,
+            None => {
+                println!("Must enter 'highlight', 'line', 'dotted', 'arrows', or 'numbers'.");
+                process::exit(1);
+            }
+        }
+    };
+
+    theme
+}
+
+fn get_custom_theme_input() -> Theme {
+    let mut theme = Theme::new();
+
+    let walls = get_text_input("Enter wall color (black, gray, navy, brown, white) [black]: ");
+    theme.walls = match walls.as_str() {
+        "black" | "" => (0.0, 0.0, 0.0),
+        "gray" => (0.3, 0.3, 0.3),
+        "navy" => (0.1, 0.15, 0.45),
+        "brown" => (0.35, 0.2, 0.1),
+        "white" => (1.0, 1.0, 1.0),
+        _ => {
+            println!("Must enter 'black', 'gray', 'navy', 'brown', or 'white'.");
+            process::exit(1);
+        }
+    };
+
+    let background = get_text_input("Enter background (none, cream, sky, mint, blue) [none]: ");
+    theme.background = match background.as_str() {
+        "none" | "" => None,
+        "cream" => Some((0.96, 0.91, 0.78)),
+        "sky" => Some((0.85, 0.93, 1.0)),
+        "mint" => Some((0.86, 0.97, 0.88)),
+        "blue" => Some((0.1, 0.25, 0.55)),
+        _ => {
+            println!("Must enter 'none', 'cream', 'sky', 'mint', or 'blue'.");
+            process::exit(1);
+        }
+    };
+
+    let caps = get_text_input("Enter wall ends (square, round, butt) [square]: ");
+    theme.caps = match caps.as_str() {
+        "square" | "" => LineCapStyle::ProjectingSquare,
+        "round" => LineCapStyle::Round,
+        "butt" => LineCapStyle::Butt,
+        _ => {
+            println!("Must enter 'square', 'round', or 'butt'.");
+            process::exit(1);
+        }
+    };
+
+    let joins = get_text_input("Enter wall corners (miter, round, bevel) [miter]: ");
+    theme.joins = match joins.as_str() {
+        "miter" | "" => LineJoinStyle::Miter,
+        "round" => LineJoinStyle::Round,
+        // printpdf calls the bevel join Limit
+        "bevel" => LineJoinStyle::Limit,
+        _ => {
+            println!("Must enter 'miter', 'round', or 'bevel'.");
+            process::exit(1);
+        }
+    };
+
+    let line = get_text_input("Enter wall line (solid, dashed) [solid]: ");
+    theme.dashed = match line.as_str() {
+        "solid" | "" => false,
+        "dashed" => true,
+        _ => {
+            println!("Must enter 'solid' or 'dashed'.");
+            process::exit(1);
+        }
+    };
+
+    theme
+}
+
+// Returns how the tips grow.  A difficulty tries split chances of its own,
+// so the split chance is only asked for without one.
+fn get_growth_input(ask_split_chance: bool) -> Growth {
+    let mut growth = Growth::new();
+
+    if ask_split_chance {
+        growth.split_chance = get_float_input_or_default("Enter chance of a path splitting (0..1) [0.05]: ", growth::SPLIT_CHANCE);
+        if !(0.0..=1.0).contains(&growth.split_chance) {
+            println!("Must enter value between 0 and 1.");
+            process::exit(1);
+        }
+    }
+
+    let max_tips = get_float_input_or_default("Enter maximum number of growing paths (0 for no limit) [0]: ", 0.0);
+    if max_tips < 0.0 {
+        println!("Must enter a value of 0 or more.");
+        process::exit(1);
+    }
+    if max_tips >= 1.0 {
+        growth.max_tips = Some(max_tips as usize);
+    }
+
+    let schedule = get_text_input("Enter path order (round-robin, random, oldest, newest) [round-robin]: ");
+    growth.schedule = match schedule.as_str() {
+        "" => Schedule::RoundRobin,
+        _ => match Schedule::named(&schedule) {
+            Some(schedule) => schedule,
+            None => {
+                println!("Must enter 'round-robin', 'random', 'oldest', or 'newest'.");
+                process::exit(1);
+            }
+        }
+    };
+
+    growth
+}
+
+fn get_symmetry_input() -> Symmetry {
+    let symmetry = get_text_input("Enter symmetry (none, horizontal, vertical, rotational) [none]: ");
+    match symmetry.as_str() {
+        "" => Symmetry::None,
+        _ => match Symmetry::named(&symmetry) {
+            Some(symmetry) => symmetry,
+            None => {
+                println!("Must enter 'none', 'horizontal', 'vertical', or 'rotational'.");
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn get_weave_input() -> bool {
+    let style = get_text_input("Enter maze style (standard, weave) [standard]: ");
+    match style.as_str() {
+        "standard" | "" => false,
+        "weave" => true,
+        _ => {
+            println!("Must enter 'standard' or 'weave'.");
+            process::exit(1);
+        }
+    }
+}
+
+fn get_levels_input() -> u32 {
+    let levels = get_float_input_or_default("Enter number of levels, one page each (1..10) [1]: ", 1.0);
+    if !(1.0..=10.0).contains(&levels) {
+        println!("Must enter value between 1 and 10.");
+        process::exit(1);
+    }
+
+    levels as u32
+}
+
+fn get_topology_input() -> Topology {
+    let topology = get_text_input("Enter topology (plane, cylinder, torus, moebius) [plane]: ");
+    match topology.as_str() {
+        "" => Topology::Plane,
+        _ => match Topology::named(&topology) {
+            Some(topology) => topology,
+            None => {
+                println!("Must enter 'plane', 'cylinder', 'torus', or 'moebius'.");
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn get_doors_input() -> usize {
+    let doors = get_float_input_or_default("Enter number of locked doors (0..5) [0]: ", 0.0);
+    if !(0.0..=5.0).contains(&doors) {
+        println!("Must enter value between 0 and 5.");
+        process::exit(1);
+    }
+
+    doors as usize
+}
+
+fn get_checkpoints_input() -> usize {
+    let checkpoints = get_float_input_or_default("Enter number of checkpoints to collect (0..10) [0]: ", 0.0);
+    if !(0.0..=10.0).contains(&checkpoints) {
+        println!("Must enter value between 0 and 10.");
+        process::exit(1);
+    }
+
+    checkpoints as usize
+}
+
+// Ask whether the maze should fit on one page, or be tiled across several
+// sheets of paper as a poster.
+fn get_layout_input() -> Option<Poster> {
+    let layout = get_text_input("Enter layout (single, poster) [single]: ");
+    match layout.as_str() {
+        "single" | "" => None,
+        "poster" => {
+            let sheets_across = get_float_input("Enter number of sheets across (1..20): ");
+            let sheets_down = get_float_input("Enter number of sheets down (1..20): ");
+            if !(1.0..=20.0).contains(&sheets_across) || !(1.0..=20.0).contains(&sheets_down) {
+                println!("Must enter value between 1 and 20.");
+                process::exit(1);
+            }
+
+            Some(Poster::new(sheets_across as u32, sheets_down as u32))
+        },
+        _ => {
+            println!("Must enter 'single' or 'poster'.");
+            process::exit(1);
+        }
+    }
+}
+
+// Returns the title to print below the maze, or None for no caption.  Unlike
+// other answers, the title keeps its capitals.
+fn get_title_input() -> Option<String> {
+    print!("Enter title to print with the difficulty, seed and size (none, or the title) [none]: ");
+    io::stdout().flush().unwrap();
+
+    let mut response = String::new();
+
+    io::stdin()
+        .read_line(&mut response)
+        .expect("Failed to read line");
+    let title = response.trim();
+    if title.chars().count() > 60 {
+        println!("Must enter a title of at most 60 characters.");
+        process::exit(1);
+    }
+
+    match title.to_lowercase().as_str() {
+        "none" | "" => None,
+        _ => Some(title.to_string())
+    }
+}
+
 fn get_text_input(prompt: &str) -> String {
     print!("{}", prompt);
     io::stdout().flush().unwrap();
@@ -830,36 +1767,325 @@ fn get_float_input(prompt: &str) -> f64 {
     response
 }
 
+// Like get_float_input(), but an empty response gives the default value.
+fn get_float_input_or_default(prompt: &str, default: f64) -> f64 {
+    print!("{}", prompt);
+    io::stdout().flush().unwrap();
+
+    let mut response = String::new();
+
+    io::stdin()
+        .read_line(&mut response)
+        .expect("Failed to read line");
+    if response.trim().is_empty() {
+        return default;
+    }
+
+    match response.trim().parse::<f64>() {
+        Ok(value) => value,
+        Err(_err) => {
+            println!("Must enter a numeric value.");
+            process::exit(1);
+        }
+    }
+}
+
 
 fn main() {
+    // 'maze benchmark' times generation and rendering instead of prompting
+    if std::env::args().nth(1).as_deref() == Some("benchmark") {
+        benchmark::run();
+        return;
+    }
+
+    // 'maze regenerate <file>' makes a maze again from the settings recorded
+    // in its PDF
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("regenerate") {
+        regenerate::run(&args[2..]);
+        return;
+    }
+
     // Prompt user for inputs
     let (cell_size, line_width_pt, page_width_in, page_height_in) = get_user_input();
+    let output = get_output_input(cell_size);
+    let poster = match output {
+        Output::Pdf => get_layout_input(),
+        _ => None
+    };
 
-    // Determine page margin based on page size
-    let mut margin_in: f64 = 0.5;
-    if page_width_in > 11.0 && page_height_in > 17.0 {
-        margin_in = 1.0;
-    }
+    // Crossings are only drawn in the formats that can show them.
+    let weave = match output {
+        Output::Pdf | Output::Plotter(_) | Output::Graph(_) => get_weave_input(),
+        _ => false
+    };
 
-    let x_size = ((page_width_in - margin_in * 2.0) / (cell_size)) as u32;
-    let y_size = ((page_height_in - margin_in * 2.0) / (cell_size)) as u32;
-    let mut maze: Maze = Maze::new(x_size, y_size, 3);
+    // Wrap-around openings are labelled in the margin, which a poster trims
+    // off.
+    let topology = match (&output, &poster) {
+        (Output::Pdf, None) | (Output::Graph(_), _) | (Output::Play { .. }, _) => get_topology_input(),
+        _ => Topology::Plane
+    };
 
-    let start = Instant::now();
-    maze.generate();
+    // Levels are drawn one per page, so only single page PDFs have them.  A
+    // torus would need the edge between two levels to be open differently
+    // on each side.
+    let levels = match (&output, &poster) {
+        (Output::Pdf, None) if topology != Topology::Torus => get_levels_input(),
+        _ => 1
+    };
 
-    println!("Generated maze in {} milliseconds.", start.elapsed().as_millis());
+    // Doors and keys are drawn in the maze, so a poster could split them
+    // from their letters.
+    let doors = match (&output, &poster) {
+        (Output::Pdf, None) => get_doors_input(),
+        _ => 0
+    };
 
-    let doc: Document = Document {
+    // The solution shows one route, so checkpoints and doors are not mixed.
+    let checkpoints = match output {
+        Output::Pdf if doors == 0 => get_checkpoints_input(),
+        _ => 0
+    };
+
+    // A race has its own solution, one route per player, drawn on a single
+    // page.
+    let race_tolerance = match (&output, &poster) {
+        (Output::Pdf, None) if levels == 1 && doors == 0 && checkpoints == 0 => get_race_input(),
+        _ => None
+    };
+
+    // Mazes are generated until one meets the difficulty, so an animation
+    // and a race, which has its own search, go without.
+    let difficulty = match output {
+        Output::Animation(_) | Output::Labyrinth(_) => None,
+        _ if race_tolerance.is_some() => None,
+        _ => get_difficulty_input()
+    };
+
+    // Only plain single level mazes are mirrored, and an animation would
+    // only show the generated half.
+    let symmetry = match output {
+        Output::Animation(_) | Output::Labyrinth(_) => Symmetry::None,
+        _ if weave || topology != Topology::Plane || levels > 1 => Symmetry::None,
+        _ => get_symmetry_input()
+    };
+
+    // Rooms are laid out before generation, so they are left out of mazes
+    // that are generated in halves or tunnel under their corridors.
+    let (rooms, room_labels) = match output {
+        Output::Animation(_) | Output::Labyrinth(_) => (0, false),
+        _ if weave || levels > 1 || symmetry != Symmetry::None => (0, false),
+        Output::Pdf => get_rooms_input(true),
+        _ => get_rooms_input(false)
+    };
+
+    let heat_map = match output {
+        Output::Pdf => get_heat_map_input(),
+        _ => None
+    };
+
+    // Crossings are drawn inside the cells, where thick walls would cover
+    // them.  The other formats draw blocks already, or have their own wall
+    // thickness.
+    let blocks = match output {
+        Output::Pdf | Output::Plotter(_) if !weave => get_blocks_input(),
+        _ => None
+    };
+
+    let theme = match output {
+        Output::Pdf => get_theme_input(),
+        _ => Theme::new()
+    };
+
+    // The caption is printed in the margin of a single page.
+    let title = match (&output, &poster) {
+        (Output::Pdf, None) if levels == 1 => get_title_input(),
+        _ => None
+    };
+
+    let growth = match output {
+        Output::Labyrinth(_) => Growth::new(),
+        _ => get_growth_input(difficulty.is_none())
+    };
+
+    // Block walls are drawn as lines as wide as the walls, which with square
+    // caps fill the same squares as the blocks.
+    let line_width_pt = match blocks {
+        Some(ratio) => cell_size * 72.0 / (1.0 + ratio),
+        None => line_width_pt
+    };
+
+    let margin_in = page_margin(page_width_in, page_height_in);
+
+    let mut doc: Document = Document {
         page_height_in,
         page_width_in,
         line_width_pt,
-        margin_in
+        margin_in,
+        theme,
+        blocks,
+        caption: Caption { title: title.clone(), settings: Vec::new() }
+    };
+
+    // A poster maze covers the area of all the sheets once assembled.
+    let maze_doc: Document = match &poster {
+        Some(poster) => poster.assembled_document(&doc),
+        None => doc.clone()
+    };
+
+    let x_size = ((maze_doc.page_width_in - margin_in * 2.0) / (cell_size)) as u32;
+    let y_size = ((maze_doc.page_height_in - margin_in * 2.0) / (cell_size)) as u32;
+    let (x_size, y_size) = symmetry.fit_size(x_size, y_size);
+
+    // A labyrinth is laid out on its own grid rather than generated as a
+    // maze of this size.
+    if let Output::Labyrinth(kind) = &output {
+        let start = Instant::now();
+        let labyrinth = match kind {
+            LabyrinthKind::Unicursal => Labyrinth::unicursal(x_size, y_size),
+            LabyrinthKind::Classical(circuits) => Labyrinth::classical(*circuits,
+                (page_height_in - margin_in * 2.0) / (page_width_in - margin_in * 2.0))
+        };
+        labyrinth.to_pdf(&doc, false, "maze.pdf");
+        labyrinth.to_pdf(&doc, true, "solution.pdf");
+
+        println!("Generated labyrinth with a path of {} cells in {} milliseconds.", labyrinth.path_length(), start.elapsed().as_millis());
+        return;
+    }
+
+    let settings = Settings {
+        x_size,
+        y_size,
+        levels,
+        weave,
+        topology,
+        symmetry,
+        rooms,
+        room_labels,
+        doors,
+        checkpoints,
+        race_tolerance,
+        growth,
+        heat_map,
+        page_width_in,
+        page_height_in,
+        line_width_pt,
+        poster,
+        blocks,
+        theme,
+        title
+    };
+
+    let new_maze = |tuning: &Tuning| settings.new_maze(tuning);
+    let start = Instant::now();
+    let mut maze = match &difficulty {
+        Some(difficulty) => {
+            let (maze, tuning, (winding, decisions), met) = difficulty::generate_for_difficulty(new_maze, difficulty);
+            if !met {
+                println!("No maze of this size and shape had a solution {} to {} times a straight run, with {:.0}% to {:.0}% of it decisions.",
+                    difficulty.winding.0, difficulty.winding.1, difficulty.decisions.0 * 100.0, difficulty.decisions.1 * 100.0);
+            }
+            println!("{} seed {}, with split chance {} and corridor weight {}: the solution is {:.2} times a straight run, {:.1}% of it decisions.",
+                if met { "Chose" } else { "The closest was" },
+                tuning.seed, tuning.split_chance, tuning.corridor_weight, winding, decisions * 100.0);
+            maze
+        },
+        None => new_maze(&Tuning::new(growth.split_chance))
     };
 
-    maze.to_pdf(&doc, false, "maze.pdf");
-    maze.to_pdf(&doc, true, "solution.pdf");
+    if let Output::Animation(_) = output {
+        maze.record_history();
+    }
+
+    if difficulty.is_none() {
+        maze.generate();
+    }
 
-    println!("Generated PDFs in {} milliseconds.", start.elapsed().as_millis());
+    println!("Generated maze in {} milliseconds.", start.elapsed().as_millis());
+
+    if maze.rooms.len() < rooms {
+        println!("Only {} of {} rooms fit in the maze.", maze.rooms.len(), rooms);
+    }
 
+    if doors > 0 {
+        match maze.add_doors(doors) {
+            Some((placed, moves)) => println!("Locked doors placed: {}; the solver's route takes {} moves.", placed, moves),
+            None => println!("The solution is too short for locked doors.")
+        }
+    }
+
+    if checkpoints > 0 {
+        let moves = maze.add_checkpoints(checkpoints);
+        println!("Placed {} checkpoints; the shortest tour takes {} moves.", checkpoints, moves);
+    }
+
+    // Mazes are generated until one has a fair place for the goal.
+    if let Some(tolerance) = race_tolerance {
+        let mut attempts = 1;
+        let stats = loop {
+            if let Some(stats) = maze.add_race(tolerance) {
+                break Some(stats);
+            }
+            if attempts == RACE_ATTEMPTS {
+                break None;
+            }
+            maze = new_maze(&Tuning::new(growth.split_chance));
+            maze.generate();
+            attempts += 1;
+        };
+
+        match stats {
+            Some([(moves1, decisions1), (moves2, decisions2)]) => println!(
+                "Race goal placed after {} mazes: player 1 takes {} moves with {} decisions, player 2 takes {} moves with {} decisions.",
+                attempts, moves1, decisions1, moves2, decisions2),
+            None => println!("No fair race goal found in {} mazes; the last one is written without a race.", attempts)
+        }
+    }
+
+    // Everything needed to generate the maze again is recorded in the PDFs.
+    doc.caption.settings = settings.record(&maze);
+
+    match &output {
+        Output::Pdf => {
+            maze.write_pdf(&doc, settings.poster.as_ref(), false, "maze.pdf");
+            maze.write_pdf(&doc, settings.poster.as_ref(), true, "solution.pdf");
+
+            println!("Generated PDFs in {} milliseconds.", start.elapsed().as_millis());
+        },
+        Output::Plotter(format) => {
+            let filename = format.filename();
+            let (travel_mm, unordered_travel_mm) = maze.to_plotter(&doc, format, filename);
+
+            println!("Generated {} in {} milliseconds.", filename, start.elapsed().as_millis());
+            println!("Pen-up travel is {:.0} mm ({:.0} mm before ordering strokes).", travel_mm, unordered_travel_mm);
+        },
+        Output::Laser(settings) => {
+            let filename = settings.filename();
+            let outlines = maze.to_laser(settings, filename);
+
+            println!("Generated {} with {} outlines in {} milliseconds.", filename, outlines, start.elapsed().as_millis());
+        },
+        Output::Stl(settings) => {
+            let triangles = maze.to_stl(settings, "maze.stl");
+
+            println!("Generated maze.stl with {} triangles in {} milliseconds.", triangles, start.elapsed().as_millis());
+        },
+        Output::Graph(format) => {
+            let filename = format.filename();
+            let (nodes, edges) = maze.to_graph(format, filename);
+
+            println!("Generated {} with {} nodes and {} edges in {} milliseconds.", filename, nodes, edges, start.elapsed().as_millis());
+        },
+        Output::Play { fog } => {
+            maze.play(*fog);
+        },
+        Output::Animation(settings) => {
+            let filename = settings.filename();
+            let frames = maze.to_animation(settings, filename);
+
+            println!("Generated {} with {} frames in {} milliseconds.", filename, frames, start.elapsed().as_millis());
+        },
+        Output::Labyrinth(_) => unreachable!("labyrinths are written before a maze is generated")
+    }
 }
@@ -0,0 +1,256 @@
+// Terminal play mode.  The player walks from the start to the finish area
+// with the arrow keys or N, S, E and W, and can only pass where the maze has
+// a passage.  With fog of war on, only the walls around cells the player has
+// been to are shown.  When stdin is not a terminal, moves are read a line at
+// a time instead, as strings of N, S, E and W.
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::tty::IsTty;
+use crossterm::{execute, queue};
+use std::io::{self, BufRead, Write};
+use std::process;
+use std::time::Instant;
+
+use crate::{Direction, Maze};
+use crate::walls::WallBlocks;
+
+// Rows kept free below the maze for the status line
+const STATUS_ROWS: u16 = 2;
+
+struct Game<'a> {
+    maze: &'a Maze,
+    blocks: WallBlocks,
+    fog: bool,
+    seen: Vec<bool>,
+    x: u32,
+    y: u32,
+    moves: u32,
+    started: Instant
+}
+
+enum Command {
+    Move(Direction),
+    Quit,
+    Ignore
+}
+
+// Raw mode on the alternate screen, with the cursor hidden.  The terminal is
+// put back however play ends, including on an error.
+struct RawScreen;
+
+impl RawScreen {
+    fn enter() -> io::Result<RawScreen> {
+        terminal::enable_raw_mode()?;
+        let screen = RawScreen;
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(screen)
+    }
+}
+
+impl Drop for RawScreen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Maze {
+    // Play the maze in the terminal until the finish area is reached or the
+    // player quits.
+    pub fn play(&self, fog: bool) {
+        let mut game = Game {
+            maze: self,
+            blocks: self.wall_blocks(),
+            fog,
+            seen: vec![false; self.flags.len()],
+            x: self.start_x,
+            y: self.start_y,
+            moves: 0,
+            started: Instant::now()
+        };
+        game.reveal();
+
+        let played = if io::stdin().is_tty() {
+            game.play_keys()
+        } else {
+            game.play_lines()
+        };
+        let finished = match played {
+            Ok(finished) => finished,
+            Err(error) => {
+                println!("Could not play in the terminal: {}", error);
+                process::exit(1);
+            }
+        };
+
+        if finished {
+            println!("You reached the finish in {} moves and {} seconds.", game.moves, game.started.elapsed().as_secs());
+            println!("The shortest route takes {} moves.", self.solution_path().len() - 1);
+        } else {
+            println!("Gave up after {} moves.", game.moves);
+        }
+    }
+}
+
+impl<'a> Game<'a> {
+    // Read single key presses in raw mode, redrawing after each one.
+    fn play_keys(&mut self) -> io::Result<bool> {
+        let mut stdout = io::stdout();
+        let _screen = RawScreen::enter()?;
+
+        let mut finished = false;
+        loop {
+            self.draw_screen(&mut stdout)?;
+
+            if let Event::Key(KeyEvent { code, kind: KeyEventKind::Press, .. }) = event::read()? {
+                let command = match code {
+                    KeyCode::Up | KeyCode::Char('n') => Command::Move(Direction::North),
+                    KeyCode::Down | KeyCode::Char('s') => Command::Move(Direction::South),
+                    KeyCode::Right | KeyCode::Char('e') => Command::Move(Direction::East),
+                    KeyCode::Left | KeyCode::Char('w') => Command::Move(Direction::West),
+                    KeyCode::Char('q') | KeyCode::Esc => Command::Quit,
+                    _ => Command::Ignore
+                };
+
+                match command {
+                    Command::Move(direction) => {
+                        if self.step(&direction) {
+                            finished = true;
+                            break;
+                        }
+                    },
+                    Command::Quit => break,
+                    Command::Ignore => ()
+                }
+            }
+        }
+
+        Ok(finished)
+    }
+
+    // Read lines of moves such as "nneesw", printing the maze after each.
+    fn play_lines(&mut self) -> io::Result<bool> {
+        let stdin = io::stdin();
+        let mut stdout = io::stdout();
+
+        writeln!(stdout, "{}", self.view(self.blocks.columns, self.blocks.rows).join("\n"))?;
+        for line in stdin.lock().lines() {
+            for command in line?.to_lowercase().chars() {
+                let direction = match command {
+                    'n' => Direction::North,
+                    's' => Direction::South,
+                    'e' => Direction::East,
+                    'w' => Direction::West,
+                    'q' => return Ok(false),
+                    _ => continue
+                };
+
+                if self.step(&direction) {
+                    return Ok(true);
+                }
+            }
+
+            writeln!(stdout, "{}", self.view(self.blocks.columns, self.blocks.rows).join("\n"))?;
+            writeln!(stdout, "Moves: {}", self.moves)?;
+        }
+
+        Ok(false)
+    }
+
+    // Move the player if there is a passage.  Returns true once the finish
+    // area is reached.
+    fn step(&mut self, direction: &Direction) -> bool {
+        if let Some(next) = self.maze.get_passage(&self.maze.get_cell(self.x, self.y), direction) {
+            self.x = next.x;
+            self.y = next.y;
+            self.moves += 1;
+            self.reveal();
+        }
+
+        self.maze.get_cell(self.x, self.y).finish_area()
+    }
+
+    fn reveal(&mut self) {
+        let index = self.maze.index(self.x, self.y);
+        self.seen[index] = true;
+    }
+
+    fn draw_screen<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let columns = (width / 2) as i32;
+        let rows = height.saturating_sub(STATUS_ROWS) as i32;
+
+        queue!(out, MoveTo(0, 0), Clear(ClearType::All))?;
+        for line in self.view(columns, rows) {
+            write!(out, "{}\r\n", line)?;
+        }
+        write!(out, "Moves: {}  Time: {} s  (arrow keys or n/s/e/w to move, q to quit)",
+            self.moves, self.started.elapsed().as_secs())?;
+        out.flush()
+    }
+
+    // The part of the block grid around the player that fits in the given
+    // number of block columns and rows, top row first.  Each block is two
+    // characters wide, so cells look roughly square.
+    fn view(&self, columns: i32, rows: i32) -> Vec<String> {
+        let player = (2 * self.x as i32 + 1, 2 * self.y as i32 + 1);
+        let first = |center: i32, count: i32, total: i32| (center - count / 2).min(total - count).max(0);
+        let first_column = first(player.0, columns, self.blocks.columns);
+        let first_row = first(player.1, rows, self.blocks.rows);
+
+        let mut lines: Vec<String> = Vec::new();
+        for row in (first_row..(first_row + rows).min(self.blocks.rows)).rev() {
+            let mut line = String::new();
+            for column in first_column..(first_column + columns).min(self.blocks.columns) {
+                line.push_str(self.block_text(column, row, player));
+            }
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    fn block_text(&self, column: i32, row: i32, player: (i32, i32)) -> &'static str {
+        if (column, row) == player {
+            return "@@";
+        }
+
+        if !self.is_visible(column, row) {
+            return "  ";
+        }
+
+        if self.blocks.is_solid(column, row) {
+            "██"
+        } else if column % 2 == 1 && row % 2 == 1 &&
+                  self.maze.get_cell(column as u32 / 2, row as u32 / 2).finish_area() {
+            "**"
+        } else if column % 2 == 1 && row % 2 == 1 && self.fog &&
+                  self.seen[self.maze.index(column as u32 / 2, row as u32 / 2)] {
+            "··"
+        } else {
+            "  "
+        }
+    }
+
+    // With fog of war, a block is visible when it touches a cell the player
+    // has been to.
+    fn is_visible(&self, column: i32, row: i32) -> bool {
+        if !self.fog {
+            return true;
+        }
+
+        for cell_column in [column - 1, column, column + 1].iter() {
+            for cell_row in [row - 1, row, row + 1].iter() {
+                if cell_column % 2 == 1 && cell_row % 2 == 1 &&
+                   *cell_column < self.blocks.columns && *cell_row < self.blocks.rows &&
+                   self.seen[self.maze.index(*cell_column as u32 / 2, *cell_row as u32 / 2)] {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
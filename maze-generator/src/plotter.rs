@@ -0,0 +1,346 @@
+// Pen plotter output.  The maze walls and icons are written as HPGL or
+// G-code strokes, using the same page coordinates as the PDF.  Strokes that
+// meet end to end are drawn as one continuous path, and paths are ordered
+// nearest first so the pen spends as little time as possible travelling with
+// the pen up.
+
+use printpdf::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::{Document, Maze, transform_x, transform_y};
+use crate::walls::{get_outlines, WallBlocks};
+
+// HPGL plotter units per millimetre
+const HPGL_UNITS_PER_MM: f64 = 40.0;
+
+// G-code pen heights and feed rates
+const GCODE_PEN_UP_Z_MM: f64 = 2.0;
+const GCODE_PEN_DOWN_Z_MM: f64 = 0.0;
+const GCODE_DRAW_FEED_MM_PER_MIN: f64 = 1500.0;
+const GCODE_PEN_FEED_MM_PER_MIN: f64 = 500.0;
+
+pub enum PlotterFormat {
+    Hpgl,
+    Gcode
+}
+
+impl PlotterFormat {
+    pub fn filename(&self) -> &'static str {
+        match self {
+            PlotterFormat::Hpgl => "maze.hpgl",
+            PlotterFormat::Gcode => "maze.gcode"
+        }
+    }
+}
+
+// A stroke drawn without lifting the pen, in millimetres from the lower-left
+// corner of the page.
+type Path = Vec<(f64, f64)>;
+
+impl Maze {
+    // Write the maze for a pen plotter.  Returns the pen-up travel distance
+    // in millimetres after ordering the strokes, and before ordering them.
+    pub fn to_plotter(&self, doc: &Document, format: &PlotterFormat, filename: &str) -> (f64, f64) {
+        let paths = self.get_plotter_paths(doc);
+        let unordered_travel_mm = pen_up_travel(&paths);
+
+        let cell_mm = Mm::from(Pt((doc.page_width_in - 2.0 * doc.margin_in) * 72.0 / self.x_size as f64)).0;
+        let paths = order_paths(paths, cell_mm);
+        let travel_mm = pen_up_travel(&paths);
+
+        let mut out = BufWriter::new(File::create(filename).unwrap());
+        match format {
+            PlotterFormat::Hpgl => write_hpgl(&mut out, &paths),
+            PlotterFormat::Gcode => write_gcode(&mut out, &paths)
+        }.unwrap();
+
+        (travel_mm, unordered_travel_mm)
+    }
+
+    // The plotter draws along the centre of each wall, so unlike the PDF the
+    // points are not offset by the line width.  Block walls are outlined
+    // instead, with the wall on the far side of each grid line, as in the PDF.
+    fn get_plotter_paths(&self, doc: &Document) -> Vec<Path> {
+        let mut paths: Vec<Path> = Vec::new();
+
+        if let Some(ratio) = doc.blocks {
+            let thickness = 1.0 / (1.0 + ratio);
+            for outline in get_outlines(&self.wall_blocks()) {
+                let mut path: Path = outline.iter()
+                    .map(|(column, row)| (
+                        Mm::from(transform_x(WallBlocks::edge(*column, 1.0, thickness), self.x_size as i32, doc)).0,
+                        Mm::from(transform_y(WallBlocks::edge(*row, 1.0, thickness), self.y_size as i32, doc)).0))
+                    .collect();
+
+                path.push(path[0]);
+                paths.push(path);
+            }
+        } else {
+            for polyline in self.wall_polylines(-1..self.x_size as i32 + 1, -1..self.y_size as i32 + 1) {
+                let mut path: Path = polyline.points.iter()
+                    .map(|(x, y)| (
+                        Mm::from(transform_x(*x as f64, self.x_size as i32, doc)).0,
+                        Mm::from(transform_y(*y as f64, self.y_size as i32, doc)).0))
+                    .collect();
+
+                if polyline.closed {
+                    path.push(path[0]);
+                }
+                paths.push(path);
+            }
+        }
+
+        for points in self.crossing_walls(-1..self.x_size as i32 + 1, -1..self.y_size as i32 + 1) {
+            paths.push(points.iter()
+                .map(|(x, y)| (
+                    Mm::from(transform_x(*x, self.x_size as i32, doc)).0,
+                    Mm::from(transform_y(*y, self.y_size as i32, doc)).0))
+                .collect());
+        }
+
+        // The icons are outlined, since a pen cannot fill them.
+        for icon in self.get_icons(doc) {
+            let mut path: Path = icon.points.iter()
+                .map(|(point, _)| (Mm::from(point.x).0, Mm::from(point.y).0))
+                .collect();
+
+            path.push(path[0]);
+            paths.push(path);
+        }
+
+        paths
+    }
+}
+
+// Greedy nearest neighbour ordering.  Starting from the origin, repeatedly
+// draw the unused path with the closest end point, reversing it if its last
+// point is the closer end.  A path that starts where the previous one ended
+// is appended to it, so the pen stays down.
+fn order_paths(paths: Vec<Path>, bucket_mm: f64) -> Vec<Path> {
+    let mut index = EndPointIndex::new(&paths, bucket_mm);
+    let mut used = vec![false; paths.len()];
+    let mut ordered: Vec<Path> = Vec::new();
+    let mut position = (0.0, 0.0);
+
+    while let Some((path, reversed)) = index.take_nearest(&paths, &mut used, position) {
+        let mut next = paths[path].clone();
+        if reversed {
+            next.reverse();
+        }
+        position = *next.last().unwrap();
+
+        match ordered.last_mut() {
+            Some(last) if distance(*last.last().unwrap(), next[0]) < 1e-6 => {
+                last.extend_from_slice(&next[1..]);
+            },
+            _ => ordered.push(next)
+        }
+    }
+
+    ordered
+}
+
+// Both end points of every path, bucketed into a grid of squares so the
+// closest one can be found by searching outward from a position.
+struct EndPointIndex {
+    bucket_mm: f64,
+    buckets: HashMap<(i64, i64), Vec<(usize, bool)>>,
+    max_ring: i64
+}
+
+impl EndPointIndex {
+    fn new(paths: &[Path], bucket_mm: f64) -> EndPointIndex {
+        let mut index = EndPointIndex {
+            bucket_mm,
+            buckets: HashMap::new(),
+            max_ring: 0
+        };
+
+        for (path, points) in paths.iter().enumerate() {
+            index.insert(*points.first().unwrap(), (path, false));
+            index.insert(*points.last().unwrap(), (path, true));
+        }
+
+        index
+    }
+
+    fn bucket(&self, point: (f64, f64)) -> (i64, i64) {
+        ((point.0 / self.bucket_mm).floor() as i64, (point.1 / self.bucket_mm).floor() as i64)
+    }
+
+    fn insert(&mut self, point: (f64, f64), end: (usize, bool)) {
+        let bucket = self.bucket(point);
+        self.max_ring = self.max_ring.max(bucket.0.abs() + 1).max(bucket.1.abs() + 1);
+        self.buckets.entry(bucket).or_default().push(end);
+    }
+
+    // Search rings of buckets around the position until no closer end point
+    // can exist.  The chosen path is marked used.  The second value is true
+    // when the path should be drawn from its last point.
+    fn take_nearest(&mut self, paths: &[Path], used: &mut [bool], position: (f64, f64)) -> Option<(usize, bool)> {
+        let (center_x, center_y) = self.bucket(position);
+        let mut best: Option<((usize, bool), f64)> = None;
+
+        for ring in 0..=(self.max_ring + center_x.abs() + center_y.abs()) {
+            // Every point in this ring is at least (ring - 1) buckets away.
+            if let Some((_, best_distance)) = best {
+                if best_distance < (ring - 1) as f64 * self.bucket_mm {
+                    break;
+                }
+            }
+
+            for bucket_x in center_x - ring..=center_x + ring {
+                for bucket_y in center_y - ring..=center_y + ring {
+                    if (bucket_x - center_x).abs() != ring && (bucket_y - center_y).abs() != ring {
+                        continue;
+                    }
+
+                    if let Some(ends) = self.buckets.get_mut(&(bucket_x, bucket_y)) {
+                        ends.retain(|(path, _)| !used[*path]);
+
+                        for (path, reversed) in ends.iter() {
+                            let points = &paths[*path];
+                            let point = if *reversed { *points.last().unwrap() } else { points[0] };
+                            let d = distance(position, point);
+                            if best.map_or(true, |(_, best_distance)| d < best_distance) {
+                                best = Some(((*path, *reversed), d));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        best.map(|((path, reversed), _)| {
+            used[path] = true;
+            (path, reversed)
+        })
+    }
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+// Total distance travelled with the pen up, starting from the origin.
+fn pen_up_travel(paths: &[Path]) -> f64 {
+    let mut position = (0.0, 0.0);
+    let mut travel = 0.0;
+
+    for path in paths {
+        travel += distance(position, path[0]);
+        position = *path.last().unwrap();
+    }
+
+    travel
+}
+
+fn write_hpgl<W: Write>(out: &mut W, paths: &[Path]) -> std::io::Result<()> {
+    let units = |value: f64| (value * HPGL_UNITS_PER_MM).round() as i64;
+
+    writeln!(out, "IN;SP1;")?;
+    for path in paths {
+        writeln!(out, "PU{},{};", units(path[0].0), units(path[0].1))?;
+
+        let points: Vec<String> = path[1..].iter()
+            .map(|(x, y)| format!("{},{}", units(*x), units(*y)))
+            .collect();
+        writeln!(out, "PD{};", points.join(","))?;
+    }
+    writeln!(out, "PU0,0;SP0;")?;
+
+    Ok(())
+}
+
+fn write_gcode<W: Write>(out: &mut W, paths: &[Path]) -> std::io::Result<()> {
+    writeln!(out, "; Maze drawn with a pen plotter")?;
+    writeln!(out, "G21 ; millimetres")?;
+    writeln!(out, "G90 ; absolute coordinates")?;
+    writeln!(out, "G0 Z{:.2}", GCODE_PEN_UP_Z_MM)?;
+
+    for path in paths {
+        writeln!(out, "G0 X{:.3} Y{:.3}", path[0].0, path[0].1)?;
+        writeln!(out, "G1 Z{:.2} F{:.0}", GCODE_PEN_DOWN_Z_MM, GCODE_PEN_FEED_MM_PER_MIN)?;
+        writeln!(out, "G1 F{:.0}", GCODE_DRAW_FEED_MM_PER_MIN)?;
+        for (x, y) in path[1..].iter() {
+            writeln!(out, "G1 X{:.3} Y{:.3}", x, y)?;
+        }
+        writeln!(out, "G0 Z{:.2}", GCODE_PEN_UP_Z_MM)?;
+    }
+
+    writeln!(out, "G0 X0 Y0")?;
+    writeln!(out, "M2")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{distance, order_paths, pen_up_travel, write_gcode, write_hpgl, Path};
+    use rand::prelude::*;
+
+    #[test]
+    fn paths_are_drawn_nearest_first_and_joined_end_to_end() {
+        let paths: Vec<Path> = vec![
+            vec![(10.0, 0.0), (20.0, 0.0)],
+            vec![(0.0, 5.0), (0.0, 1.0)],
+            vec![(20.0, 0.0), (20.0, 10.0)]
+        ];
+        let ordered = order_paths(paths, 4.0);
+
+        // The second path is drawn backwards, from its end nearest the
+        // origin, and the third carries on from the end of the first.
+        assert_eq!(ordered, vec![
+            vec![(0.0, 1.0), (0.0, 5.0)],
+            vec![(10.0, 0.0), (20.0, 0.0), (20.0, 10.0)]
+        ]);
+        assert!((pen_up_travel(&ordered) - (1.0 + 125f64.sqrt())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bucketed_search_finds_the_nearest_end() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let paths: Vec<Path> = (0..300)
+            .map(|_| vec![(rng.gen_range(0.0, 200.0), rng.gen_range(0.0, 250.0)), (rng.gen_range(0.0, 200.0), rng.gen_range(0.0, 250.0))])
+            .collect();
+
+        // The same greedy order, searching every remaining path each time
+        let mut remaining = paths.clone();
+        let mut expected: Vec<Path> = Vec::new();
+        let mut position = (0.0, 0.0);
+        while !remaining.is_empty() {
+            let (nearest, reversed) = (0..remaining.len())
+                .flat_map(|path| vec![(path, false), (path, true)])
+                .min_by(|a, b| {
+                    let end = |(path, reversed): (usize, bool)| if reversed { remaining[path][1] } else { remaining[path][0] };
+                    distance(position, end(*a)).total_cmp(&distance(position, end(*b)))
+                })
+                .unwrap();
+            let mut path = remaining.remove(nearest);
+            if reversed {
+                path.reverse();
+            }
+            position = path[1];
+            expected.push(path);
+        }
+
+        assert_eq!(order_paths(paths, 5.0), expected);
+    }
+
+    #[test]
+    fn strokes_are_written_in_plotter_units() {
+        let paths: Vec<Path> = vec![vec![(1.0, 2.0), (1.0, 12.5), (3.25, 12.5)]];
+
+        let mut hpgl: Vec<u8> = Vec::new();
+        write_hpgl(&mut hpgl, &paths).unwrap();
+        assert_eq!(String::from_utf8(hpgl).unwrap(), "IN;SP1;\nPU40,80;\nPD40,500,130,500;\nPU0,0;SP0;\n");
+
+        let mut gcode: Vec<u8> = Vec::new();
+        write_gcode(&mut gcode, &paths).unwrap();
+        let gcode = String::from_utf8(gcode).unwrap();
+        let strokes: Vec<&str> = gcode.lines().filter(|line| line.starts_with("G0 X") || line.starts_with("G1 X")).collect();
+        assert_eq!(strokes, vec!["G0 X1.000 Y2.000", "G1 X1.000 Y12.500", "G1 X3.250 Y12.500", "G0 X0 Y0"]);
+    }
+}
@@ -0,0 +1,252 @@
+// Poster layout.  A maze that is too large for one sheet of paper is split
+// into tiles, one tile per sheet.  Neighbouring tiles share a strip of
+// overlap; registration marks inside the overlap are used to line the sheets
+// up, and crop marks in the margin show where to trim.
+
+use printpdf::*;
+use printpdf::utils::calculate_points_for_circle;
+use std::cmp;
+use std::ops::Range;
+
+use crate::content::StreamedContent;
+use crate::{caption, Document, Maze};
+
+const POINTS_PER_INCH: f64 = 72.0;
+
+pub struct Poster {
+    sheets_across: u32,
+    sheets_down: u32,
+    overlap_in: f64
+}
+
+impl Poster {
+    pub fn new(sheets_across: u32, sheets_down: u32) -> Poster {
+        Poster {
+            sheets_across,
+            sheets_down,
+            overlap_in: 0.5
+        }
+    }
+
+    // The layout as recorded in a PDF's settings: sheets across by sheets
+    // down.
+    pub fn sheets(&self) -> String {
+        format!("{}x{}", self.sheets_across, self.sheets_down)
+    }
+
+    // Returns a document the size of the assembled poster.  The poster keeps
+    // the margin of a single sheet around its outside edge.
+    pub fn assembled_document(&self, sheet: &Document) -> Document {
+        let (printable_width_in, printable_height_in) = printable_size(sheet);
+        let across = self.sheets_across as f64;
+        let down = self.sheets_down as f64;
+
+        Document {
+            page_width_in: 2.0 * sheet.margin_in + across * printable_width_in - (across - 1.0) * self.overlap_in,
+            page_height_in: 2.0 * sheet.margin_in + down * printable_height_in - (down - 1.0) * self.overlap_in,
+            line_width_pt: sheet.line_width_pt,
+            margin_in: sheet.margin_in,
+            theme: sheet.theme,
+            blocks: sheet.blocks,
+            caption: sheet.caption.clone()
+        }
+    }
+
+    // Position on the assembled poster, in inches, of the lower-left corner
+    // of the printable area of a sheet.  Row 0 is the top row of sheets.
+    fn sheet_origin(&self, sheet: &Document, column: u32, row: u32) -> (f64, f64) {
+        let (printable_width_in, printable_height_in) = printable_size(sheet);
+
+        let x = sheet.margin_in + column as f64 * (printable_width_in - self.overlap_in);
+        let y = sheet.margin_in + (self.sheets_down - 1 - row) as f64 * (printable_height_in - self.overlap_in);
+
+        (x, y)
+    }
+
+    // Registration marks sit in the middle of every overlap strip, so the
+    // same mark is printed on both sheets that share the strip.
+    fn draw_registration_marks(&self, layer: &PdfLayerReference, sheet: &Document) {
+        let (printable_width_in, printable_height_in) = printable_size(sheet);
+
+        for row in 0..self.sheets_down {
+            for column in 0..self.sheets_across {
+                let (x, y) = self.sheet_origin(sheet, column, row);
+
+                // Seam with the sheet to the right
+                if column + 1 < self.sheets_across {
+                    let (next_x, _) = self.sheet_origin(sheet, column + 1, row);
+                    draw_registration_mark(layer, next_x + self.overlap_in / 2.0, y + printable_height_in / 2.0);
+                }
+
+                // Seam with the sheet below
+                if row + 1 < self.sheets_down {
+                    draw_registration_mark(layer, x + printable_width_in / 2.0, y + self.overlap_in / 2.0);
+                }
+            }
+        }
+    }
+}
+
+impl Maze {
+    // Write a PDF with one page per poster sheet.  Pages are ordered left to
+    // right, top to bottom.
+    pub fn to_poster_pdf(&self, sheet: &Document, poster: &Poster, solution: bool, filename: &str) {
+        let poster_doc = poster.assembled_document(sheet);
+        let width = Mm::from(Pt(sheet.page_width_in * POINTS_PER_INCH));
+        let height = Mm::from(Pt(sheet.page_height_in * POINTS_PER_INCH));
+
+        let (pdf, page1, layer1) = PdfDocument::new(sheet.caption.document_title(), width, height, "Solution");
+        let font = pdf.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+        let sheet_count = poster.sheets_across * poster.sheets_down;
+
+        let mut walls = StreamedContent::new();
+        for row in 0..poster.sheets_down {
+            for column in 0..poster.sheets_across {
+                let sheet_number = row * poster.sheets_across + column + 1;
+                let (page, layer) = if sheet_number == 1 {
+                    (page1, layer1)
+                } else {
+                    pdf.add_page(width, height, "Solution")
+                };
+
+                // Shift the assembled poster so this sheet's tile lands in
+                // the printable area of the page.
+                let (origin_x, origin_y) = poster.sheet_origin(sheet, column, row);
+                let shift_x = sheet.margin_in - origin_x;
+                let shift_y = sheet.margin_in - origin_y;
+
+                let solution_layer = pdf.get_page(page).get_layer(layer);
+                begin_tile(&solution_layer, sheet, shift_x, shift_y);
+                self.draw_background(&solution_layer, &poster_doc);
+                self.draw_heat_map(&solution_layer, &poster_doc);
+                if solution {
+                    self.draw_solution(&solution_layer, &font, &poster_doc);
+                }
+                solution_layer.restore_graphics_state();
+
+                let main_layer = pdf.get_page(page).add_layer("Puzzle");
+                begin_tile(&main_layer, sheet, shift_x, shift_y);
+                let (x_range, y_range) = self.tile_cells(&poster_doc, sheet, origin_x, origin_y);
+                self.draw_maze_region(&main_layer, &poster_doc, &mut walls, x_range, y_range);
+                poster.draw_registration_marks(&main_layer, sheet);
+                main_layer.restore_graphics_state();
+
+                draw_crop_marks(&main_layer, sheet);
+
+                let label = format!(
+                    "Sheet {} of {}: row {} of {}, column {} of {}.  Trim along the crop marks and line up the registration marks.",
+                    sheet_number, sheet_count, row + 1, poster.sheets_down, column + 1, poster.sheets_across);
+                main_layer.use_text(
+                    label, 8.0,
+                    Mm::from(Pt(sheet.margin_in * POINTS_PER_INCH)),
+                    Mm::from(Pt(sheet.margin_in / 3.0 * POINTS_PER_INCH)),
+                    &font);
+            }
+        }
+
+        caption::save_pdf(pdf, sheet, walls, filename);
+    }
+
+    // Range of cells, including a one cell border, that appear on the tile
+    // whose printable area starts at the given point of the poster.
+    fn tile_cells(&self, poster_doc: &Document, sheet: &Document, origin_x: f64, origin_y: f64) -> (Range<i32>, Range<i32>) {
+        let (printable_width_in, printable_height_in) = printable_size(sheet);
+        let (maze_width_in, maze_height_in) = printable_size(poster_doc);
+        let cell_width_in = maze_width_in / self.x_size as f64;
+        let cell_height_in = maze_height_in / self.y_size as f64;
+
+        let x0 = ((origin_x - poster_doc.margin_in) / cell_width_in).floor() as i32 - 1;
+        let x1 = ((origin_x + printable_width_in - poster_doc.margin_in) / cell_width_in).ceil() as i32 + 1;
+        let y0 = ((origin_y - poster_doc.margin_in) / cell_height_in).floor() as i32 - 1;
+        let y1 = ((origin_y + printable_height_in - poster_doc.margin_in) / cell_height_in).ceil() as i32 + 1;
+
+        (
+            cmp::max(x0, -1)..cmp::min(x1, self.x_size as i32 + 1),
+            cmp::max(y0, -1)..cmp::min(y1, self.y_size as i32 + 1)
+        )
+    }
+}
+
+fn printable_size(doc: &Document) -> (f64, f64) {
+    (doc.page_width_in - 2.0 * doc.margin_in, doc.page_height_in - 2.0 * doc.margin_in)
+}
+
+// Clip drawing to the printable area of the sheet and translate poster
+// coordinates onto the sheet.  Must be paired with restore_graphics_state().
+fn begin_tile(layer: &PdfLayerReference, sheet: &Document, shift_x: f64, shift_y: f64) {
+    let (printable_width_in, printable_height_in) = printable_size(sheet);
+
+    layer.save_graphics_state();
+
+    let x0 = Mm::from(Pt(sheet.margin_in * POINTS_PER_INCH));
+    let y0 = Mm::from(Pt(sheet.margin_in * POINTS_PER_INCH));
+    let x1 = Mm::from(Pt((sheet.margin_in + printable_width_in) * POINTS_PER_INCH));
+    let y1 = Mm::from(Pt((sheet.margin_in + printable_height_in) * POINTS_PER_INCH));
+    layer.add_shape(Line {
+        points: vec![
+            (Point::new(x0, y0), false),
+            (Point::new(x1, y0), false),
+            (Point::new(x1, y1), false),
+            (Point::new(x0, y1), false)],
+        is_closed: true,
+        has_fill: false,
+        has_stroke: false,
+        is_clipping_path: true,
+    });
+
+    layer.set_ctm(CurTransMat::Translate(
+        Mm::from(Pt(shift_x * POINTS_PER_INCH)),
+        Mm::from(Pt(shift_y * POINTS_PER_INCH))));
+}
+
+// A circle with a cross through it, centered on (x, y) in inches.
+fn draw_registration_mark(layer: &PdfLayerReference, x: f64, y: f64) {
+    let radius_in = 0.1;
+    let arm_in = 0.15;
+
+    layer.set_outline_thickness(0.5);
+    layer.add_shape(Line {
+        points: calculate_points_for_circle(
+            Pt(radius_in * POINTS_PER_INCH), Pt(x * POINTS_PER_INCH), Pt(y * POINTS_PER_INCH)),
+        is_closed: true,
+        has_fill: false,
+        has_stroke: true,
+        is_clipping_path: false,
+    });
+    layer.add_shape(get_mark_line(x - arm_in, y, x + arm_in, y));
+    layer.add_shape(get_mark_line(x, y - arm_in, x, y + arm_in));
+}
+
+// Short lines in the margin that extend the edges of the printable area
+// outward from each corner.
+fn draw_crop_marks(layer: &PdfLayerReference, sheet: &Document) {
+    let (printable_width_in, printable_height_in) = printable_size(sheet);
+    let gap_in = 0.0625;
+    let length_in = sheet.margin_in * 0.6;
+
+    let left = sheet.margin_in;
+    let right = sheet.margin_in + printable_width_in;
+    let bottom = sheet.margin_in;
+    let top = sheet.margin_in + printable_height_in;
+
+    layer.set_outline_thickness(0.5);
+    for (x, y, x_sign, y_sign) in [(left, bottom, -1.0, -1.0), (right, bottom, 1.0, -1.0),
+                                   (right, top, 1.0, 1.0), (left, top, -1.0, 1.0)].iter() {
+        let horizontal = get_mark_line(x + x_sign * gap_in, *y, x + x_sign * (gap_in + length_in), *y);
+        let vertical = get_mark_line(*x, y + y_sign * gap_in, *x, y + y_sign * (gap_in + length_in));
+        layer.add_shape(horizontal);
+        layer.add_shape(vertical);
+    }
+}
+
+fn get_mark_line(x1: f64, y1: f64, x2: f64, y2: f64) -> Line {
+    Line {
+        points: vec![
+            (Point::new(Mm::from(Pt(x1 * POINTS_PER_INCH)), Mm::from(Pt(y1 * POINTS_PER_INCH))), false),
+            (Point::new(Mm::from(Pt(x2 * POINTS_PER_INCH)), Mm::from(Pt(y2 * POINTS_PER_INCH))), false)],
+        is_closed: false,
+        has_fill: false,
+        has_stroke: true,
+        is_clipping_path: false,
+    }
+}
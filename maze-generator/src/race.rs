@@ -0,0 +1,224 @@
+// Two-player races.  Player one starts in the start area and player two in
+// the finish area, and both race to a shared goal near the middle of the
+// maze.  The goal is placed where the two routes to it are close in length
+// and in the number of decisions along them, a decision being a junction
+// with more than one way on.  Since the maze is a tree each route is the
+// only one, so the race is fair to within the tolerance given.  If no cell
+// near the middle is fair enough, the caller generates another maze.
+//
+// Each player's home and route are drawn in their own color, with the
+// shared goal marked by a star.  The routes are highlighted in a light tint
+// of the color, or drawn in the theme's other solution styles in the color
+// itself.
+
+use printpdf::*;
+use std::cmp::Reverse;
+
+use crate::theme::SolutionStyle;
+use crate::{get_home_icon, get_rectangle, get_star_icon, Cell, Document, Maze};
+
+#[derive(PartialEq)]
+pub struct Race {
+    goal: (u32, u32),
+    routes: [Vec<(u32, u32)>; 2]
+}
+
+// The number of moves and decisions on each player's route
+pub type RaceStats = [(usize, usize); 2];
+
+// Fraction of the maze's width and height, around the middle, that the goal
+// may be placed in
+const GOAL_REGION: f64 = 1.0 / 3.0;
+
+impl Maze {
+    // Place the shared goal where the routes from the two homes differ by
+    // at most the given number of moves and of decisions.  Of the fair
+    // enough cells, the most balanced one is taken, and then the furthest
+    // from the players.  Returns None if no cell near the middle qualifies.
+    pub fn add_race(&mut self, tolerance: usize) -> Option<RaceStats> {
+        let homes = [(self.start_x, self.start_y), (self.finish_x, self.finish_y)];
+        let searches = [self.search_from(homes[0]), self.search_from(homes[1])];
+
+        let near_middle = |x: u32, size: u32| {
+            (x as f64 + 0.5 - size as f64 / 2.0).abs() <= size as f64 * GOAL_REGION / 2.0
+        };
+
+        let mut best: Option<(usize, usize, Race, RaceStats)> = None;
+        for index in 0..self.flags.len() {
+            let cell = self.get_cell_at(index);
+            if !near_middle(cell.x, self.x_size) || !near_middle(cell.y, self.y_size) ||
+               cell.start_area() || cell.finish_area() {
+                continue;
+            }
+
+            let moves = [searches[0].distances[index] as usize, searches[1].distances[index] as usize];
+            if moves[0].abs_diff(moves[1]) > tolerance {
+                continue;
+            }
+
+            let routes = [self.get_route(&searches[0], (cell.x, cell.y)), self.get_route(&searches[1], (cell.x, cell.y))];
+            let decisions = [self.count_decisions(&routes[0]), self.count_decisions(&routes[1])];
+            if decisions[0].abs_diff(decisions[1]) > tolerance {
+                continue;
+            }
+
+            let imbalance = moves[0].abs_diff(moves[1]) + decisions[0].abs_diff(decisions[1]);
+            let length = moves[0].min(moves[1]);
+            if best.as_ref().map_or(true, |(best_imbalance, best_length, _, _)|
+                    (imbalance, Reverse(length)) < (*best_imbalance, Reverse(*best_length))) {
+                let stats = [(moves[0], decisions[0]), (moves[1], decisions[1])];
+                best = Some((imbalance, length, Race { goal: (cell.x, cell.y), routes }, stats));
+            }
+        }
+
+        let (_, _, race, stats) = best?;
+        self.race = Some(race);
+        Some(stats)
+    }
+
+    // The junctions along a route where there is more than one way on.  The
+    // start and finish areas are open rooms, so their cells are not counted.
+    pub fn count_decisions(&self, route: &[(u32, u32)]) -> usize {
+        route[..route.len() - 1].iter()
+            .map(|(x, y)| self.get_cell(*x, *y))
+            .filter(|cell: &Cell| !cell.start_area() && !cell.finish_area() && self.get_moves(cell).len() > 2)
+            .count()
+    }
+
+    // Each player's home in their color, and the goal.
+    pub fn draw_race(&self, layer: &PdfLayerReference, doc: &Document) {
+        let race = match &self.race {
+            Some(race) => race,
+            None => return
+        };
+
+        let size = self.start_finish_size;
+        let homes = [(0, 0), (self.x_size - size, self.y_size - size)];
+
+        layer.save_graphics_state();
+        for (player, (x, y)) in homes.iter().enumerate() {
+            layer.set_fill_color(player_color(player, 1.0));
+            layer.add_shape(get_home_icon(size, size, *x, *y, self.x_size, self.y_size, doc));
+        }
+        layer.restore_graphics_state();
+
+        layer.add_shape(get_star_icon(1, 1, race.goal.0, race.goal.1, self.x_size, self.y_size, doc));
+    }
+
+    // Each player's route in their color.  When highlighted, the route and
+    // home area are filled with a light tint of it.
+    pub fn draw_race_solution(&self, layer: &PdfLayerReference, font: &IndirectFontRef, doc: &Document) {
+        let race = match &self.race {
+            Some(race) => race,
+            None => return
+        };
+
+        if doc.theme.solution != SolutionStyle::Highlight {
+            for (player, route) in race.routes.iter().enumerate() {
+                self.draw_styled_route(layer, font, doc, route, player_color(player, 1.0));
+            }
+            return;
+        }
+
+        let size = self.start_finish_size;
+        let homes = [(0, 0), (self.x_size - size, self.y_size - size)];
+
+        for (player, route) in race.routes.iter().enumerate() {
+            layer.set_fill_color(player_color(player, 0.4));
+
+            let (x, y) = homes[player];
+            layer.add_shape(get_rectangle(x, y, x + size, y + size, self.x_size, self.y_size, doc));
+
+            for step in route.windows(2) {
+                // Passages that wrap around the edge join cells on opposite
+                // sides of the page, so each end is highlighted on its own.
+                if self.is_jump(step[0], step[1]) {
+                    for (x, y) in step.iter() {
+                        layer.add_shape(get_rectangle(*x, *y, x + 1, y + 1, self.x_size, self.y_size, doc));
+                    }
+                    continue;
+                }
+
+                let x0 = step[0].0.min(step[1].0);
+                let y0 = step[0].1.min(step[1].1);
+                let x1 = step[0].0.max(step[1].0) + 1;
+                let y1 = step[0].1.max(step[1].1) + 1;
+                layer.add_shape(get_rectangle(x0, y0, x1, y1, self.x_size, self.y_size, doc));
+            }
+        }
+    }
+}
+
+// Red for player one and blue for player two, at the given strength.
+fn player_color(player: usize, strength: f64) -> Color {
+    if player == 0 {
+        Color::Cmyk(Cmyk::new(0.0, 0.9 * strength, 0.9 * strength, 0.0, None))
+    } else {
+        Color::Cmyk(Cmyk::new(0.9 * strength, 0.5 * strength, 0.0, 0.0, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Maze;
+
+    // A 9 by 9 maze carved from the given paths, which start from cells
+    // carved already.
+    fn carve(paths: Vec<Vec<(u32, u32)>>) -> Maze {
+        let paths: Vec<&[(u32, u32)]> = paths.iter().map(Vec::as_slice).collect();
+        Maze::carve(9, 9, 1, &paths)
+    }
+
+    // A straight run of cells in a column, from row `from` to row `to`.
+    fn column(x: u32, from: u32, to: u32) -> Vec<(u32, u32)> {
+        if from <= to {
+            (from..=to).map(|y| (x, y)).collect()
+        } else {
+            (to..=from).rev().map(|y| (x, y)).collect()
+        }
+    }
+
+    #[test]
+    fn goal_is_the_furthest_fair_cell() {
+        // The solution runs up the left-hand column, along the middle row and
+        // up the right-hand column, with dead ends up and down from the
+        // middle row.  The one below (4, 4) is reached from (3, 3) instead,
+        // so the only fair cells near the middle are (4, 4) and (4, 5).
+        let mut paths = vec![
+            [column(0, 0, 4), (1..=8).map(|x| (x, 4)).collect(), column(8, 5, 8)].concat(),
+            column(0, 4, 8),
+            column(8, 4, 0)
+        ];
+        for x in 1..=7 {
+            paths.push(column(x, 4, 8));
+            if x != 4 {
+                paths.push(column(x, 4, 0));
+            }
+        }
+        paths.push([vec![(3, 3)], column(4, 3, 0)].concat());
+
+        let mut maze = carve(paths);
+        assert_eq!(maze.add_race(0), Some([(9, 5), (9, 5)]));
+
+        let race = maze.race.as_ref().unwrap();
+        assert_eq!(race.goal, (4, 5));
+        assert_eq!(race.routes[0], [column(0, 0, 4), (1..=4).map(|x| (x, 4)).collect(), vec![(4, 5)]].concat());
+        assert_eq!(race.routes[1], [column(8, 8, 4), (4..=7).rev().map(|x| (x, 4)).collect(), vec![(4, 5)]].concat());
+    }
+
+    #[test]
+    fn goal_is_only_as_fair_as_the_tolerance() {
+        // The solution runs along the bottom row and up the right-hand
+        // column, with a dead end up every other column.  Near the middle,
+        // the routes from the two homes differ by at least six moves.
+        let mut paths = vec![[(0..=8).map(|x| (x, 0)).collect(), column(8, 1, 8)].concat()];
+        paths.extend((0..=7).map(|x| column(x, 0, 8)));
+
+        let mut maze = carve(paths);
+        assert_eq!(maze.add_race(5), None);
+        assert!(maze.race.is_none());
+
+        assert_eq!(maze.add_race(6), Some([(10, 5), (16, 3)]));
+        assert_eq!(maze.race.as_ref().unwrap().goal, (5, 5));
+    }
+}
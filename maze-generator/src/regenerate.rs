@@ -0,0 +1,195 @@
+// Regenerating a maze from its PDF.  'maze regenerate <file> [maze|solution]'
+// reads the settings recorded in a maze PDF (see settings.rs), generates the
+// same maze again and writes its solution to solution.pdf, or the maze itself
+// to maze.pdf.  An answer key can be printed later from just the puzzle.
+
+use lopdf::{Dictionary, Object};
+use std::process;
+
+use crate::caption::Caption;
+use crate::difficulty::Tuning;
+use crate::settings::Settings;
+use crate::{page_margin, Document, Maze};
+
+pub fn run(args: &[String]) {
+    let (path, solution) = match args {
+        [path] => (path, true),
+        [path, which] if which == "solution" => (path, true),
+        [path, which] if which == "maze" => (path, false),
+        _ => {
+            println!("Usage: maze regenerate <file.pdf> [maze|solution]");
+            process::exit(1);
+        }
+    };
+
+    let (settings, tuning) = match load(path) {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            println!("{}", error);
+            process::exit(1);
+        }
+    };
+
+    let maze = rebuild(&settings, &tuning);
+    let filename = if solution { "solution.pdf" } else { "maze.pdf" };
+    maze.write_pdf(&document(&settings, &maze), settings.poster.as_ref(), solution, filename);
+
+    println!("Regenerated {} from seed {}.", filename, tuning.seed);
+}
+
+// The settings and tuning recorded in the PDF at the given path, or a message
+// saying why they could not be read.
+fn load(path: &str) -> Result<(Settings, Tuning), String> {
+    let pdf = lopdf::Document::load(path).map_err(|_| format!("Could not read {} as a PDF.", path))?;
+
+    let info = pdf.trailer.get(b"Info")
+        .and_then(Object::as_reference)
+        .and_then(|id| pdf.get_dictionary(id));
+    let (keywords, title) = match info {
+        Ok(info) => (info_text(info, b"Keywords"), info_text(info, b"Title")),
+        Err(_) => (String::new(), String::new())
+    };
+
+    // Labyrinths, and PDFs from before the settings were recorded, have none.
+    if keywords.is_empty() {
+        return Err(format!("Could not regenerate the maze in {}: it has no recorded settings.", path));
+    }
+
+    Settings::parse(&keywords, &title)
+        .map_err(|error| format!("Could not regenerate the maze in {}: {}.", path, error))
+}
+
+// The page a maze with the given settings is drawn on.
+fn document(settings: &Settings, maze: &Maze) -> Document {
+    Document {
+        page_height_in: settings.page_height_in,
+        page_width_in: settings.page_width_in,
+        line_width_pt: settings.line_width_pt,
+        margin_in: page_margin(settings.page_width_in, settings.page_height_in),
+        theme: settings.theme,
+        blocks: settings.blocks,
+        caption: Caption { title: settings.title.clone(), settings: settings.record(maze) }
+    }
+}
+
+// Generate the maze with the given settings.  Doors, checkpoints and the race
+// goal are placed after generation, the same way as when the maze was first
+// made.
+fn rebuild(settings: &Settings, tuning: &Tuning) -> Maze {
+    let mut maze = settings.new_maze(tuning);
+    maze.generate();
+    if settings.doors > 0 {
+        maze.add_doors(settings.doors);
+    }
+    if settings.checkpoints > 0 {
+        maze.add_checkpoints(settings.checkpoints);
+    }
+    if let Some(tolerance) = settings.race_tolerance {
+        maze.add_race(tolerance);
+    }
+    maze
+}
+
+// A text entry of the document information, or an empty string if it has
+// none.
+fn info_text(info: &Dictionary, key: &[u8]) -> String {
+    info.get(key)
+        .and_then(Object::as_str)
+        .map(|text| String::from_utf8_lossy(text).into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{document, load, rebuild};
+    use crate::difficulty::{self, Difficulty, Tuning};
+    use crate::growth::{Growth, Schedule};
+    use crate::heatmap::{HeatMap, Palette};
+    use crate::poster::Poster;
+    use crate::settings::Settings;
+    use crate::symmetry::Symmetry;
+    use crate::theme::{SolutionStyle, Theme};
+    use crate::topology::Topology;
+    use std::{fs, process};
+
+    fn settings() -> Settings {
+        let mut growth = Growth::new();
+        growth.max_tips = Some(8);
+        growth.schedule = Schedule::Random;
+
+        let mut theme = Theme::new();
+        theme.background = Some((0.9, 0.95, 1.0));
+        theme.solution = SolutionStyle::Arrows;
+
+        Settings {
+            x_size: 24,
+            y_size: 30,
+            levels: 1,
+            weave: false,
+            topology: Topology::Plane,
+            symmetry: Symmetry::None,
+            rooms: 0,
+            room_labels: false,
+            doors: 0,
+            checkpoints: 0,
+            race_tolerance: None,
+            growth,
+            heat_map: Some(HeatMap { palette: Palette::Ocean, neighbourhood: Some(3) }),
+            page_width_in: 8.5,
+            page_height_in: 11.0,
+            line_width_pt: 1.5,
+            poster: None,
+            blocks: Some(2.0),
+            theme,
+            title: Some("Round trip".to_string())
+        }
+    }
+
+    // Each maze is saved as a PDF, with its solution, and regenerated from
+    // the file the way 'maze regenerate' does.
+    #[test]
+    fn saved_pdfs_regenerate_the_same_maze() {
+        let weave = Settings { weave: true, topology: Topology::Torus, doors: 3, ..settings() };
+        let levels = Settings { levels: 3, topology: Topology::Moebius, checkpoints: 4, ..settings() };
+        let rooms = Settings { rooms: 4, room_labels: true, heat_map: None, ..settings() };
+        let race = Settings { race_tolerance: Some(4), blocks: None, ..settings() };
+        let poster = Settings { poster: Some(Poster::new(2, 1)), symmetry: Symmetry::Rotational, title: None, ..settings() };
+        let difficulty = settings();
+
+        let medium = Difficulty::named("medium").unwrap();
+        let (_, medium_tuning, _, _) = difficulty::generate_for_difficulty(|tuning| difficulty.new_maze(tuning), &medium);
+
+        let cases = [
+            ("weave", weave, Tuning { seed: 1, corridor_weight: 40, ..Tuning::new(0.1) }),
+            ("levels", levels, Tuning { seed: 2, ..Tuning::new(0.05) }),
+            ("rooms", rooms, Tuning { seed: 3, ..Tuning::new(0.2) }),
+            ("race", race, Tuning { seed: 6, ..Tuning::new(0.05) }),
+            ("poster", poster, Tuning { seed: 5, ..Tuning::new(0.1) }),
+            ("difficulty", difficulty, medium_tuning)
+        ];
+
+        for (name, settings, tuning) in cases.iter() {
+            let maze = rebuild(settings, tuning);
+            assert_eq!(maze.race.is_some(), settings.race_tolerance.is_some(), "{}", name);
+            let doc = document(settings, &maze);
+            let path = std::env::temp_dir().join(format!("maze-regenerate-{}-{}.pdf", process::id(), name));
+            let path = path.to_str().unwrap();
+            maze.write_pdf(&doc, settings.poster.as_ref(), true, path);
+
+            let loaded = load(path);
+            fs::remove_file(path).unwrap();
+            let (parsed, parsed_tuning) = loaded.unwrap_or_else(|error| panic!("{}: {}", name, error));
+            let again = rebuild(&parsed, &parsed_tuning);
+
+            assert!(again.flags == maze.flags, "{}: the passages differ", name);
+            assert!(again.room_cells == maze.room_cells, "{}: the rooms differ", name);
+            assert_eq!((again.finish_x, again.finish_y), (maze.finish_x, maze.finish_y), "{}", name);
+            assert_eq!(again.puzzle_route(), maze.puzzle_route(), "{}", name);
+            assert_eq!(again.tour_route(), maze.tour_route(), "{}", name);
+            assert!(again.race == maze.race, "{}: the races differ", name);
+            assert_eq!(again.measure_difficulty(), maze.measure_difficulty(), "{}", name);
+            assert_eq!(parsed.record(&again), doc.caption.settings, "{}", name);
+            assert_eq!(parsed.title, settings.title, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,267 @@
+// Settings recorded in maze PDFs.  Everything needed to generate a maze
+// again and draw it the same way is written to the keywords of its PDFs (see
+// caption.rs) as "name=value" pairs: the seed and the rest of the tuning, the
+// answers to the prompts that shape the maze, and the page and its theme.
+// Generation only draws on the random number generator started from the
+// seed, so reading the settings back and generating with them gives the same
+// maze.
+
+use printpdf::{LineCapStyle, LineJoinStyle};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::difficulty::Tuning;
+use crate::growth::{Growth, Schedule};
+use crate::heatmap::{HeatMap, Palette};
+use crate::poster::Poster;
+use crate::symmetry::Symmetry;
+use crate::theme::{SolutionStyle, Theme};
+use crate::topology::Topology;
+use crate::Maze;
+
+pub struct Settings {
+    // The size of each level in cells, and the number of levels
+    pub x_size: u32,
+    pub y_size: u32,
+    pub levels: u32,
+
+    pub weave: bool,
+    pub topology: Topology,
+    pub symmetry: Symmetry,
+    pub rooms: usize,
+    pub room_labels: bool,
+    pub doors: usize,
+    pub checkpoints: usize,
+    pub race_tolerance: Option<usize>,
+    pub growth: Growth,
+    pub heat_map: Option<HeatMap>,
+
+    // The page, and how the maze is drawn on it
+    pub page_width_in: f64,
+    pub page_height_in: f64,
+    pub line_width_pt: f64,
+    pub poster: Option<Poster>,
+    pub blocks: Option<f64>,
+    pub theme: Theme,
+    pub title: Option<String>
+}
+
+impl Settings {
+    // A maze with these settings and the given tuning, ready to generate.
+    pub fn new_maze(&self, tuning: &Tuning) -> Maze {
+        let mut maze: Maze = Maze::with_levels(self.x_size, self.y_size, self.levels, 3);
+        maze.set_growth(self.growth);
+        maze.set_tuning(tuning);
+        maze.set_weave(self.weave);
+        maze.set_topology(self.topology);
+        maze.set_symmetry(self.symmetry);
+        maze.add_rooms(self.rooms, self.room_labels);
+        maze.set_heat_map(self.heat_map);
+        maze
+    }
+
+    // The settings of a generated maze, as names and values.  The title is
+    // the PDF's own, so only whether it is printed is recorded here.
+    pub fn record(&self, maze: &Maze) -> Vec<(&'static str, String)> {
+        let theme = &self.theme;
+        vec![
+            ("seed", maze.seed.to_string()),
+            ("split-chance", maze.growth.split_chance.to_string()),
+            ("corridor-weight", maze.strategies[0].weight_east_west.to_string()),
+            ("max-paths", self.growth.max_tips.unwrap_or(0).to_string()),
+            ("path-order", self.growth.schedule.name().to_string()),
+            ("width", self.x_size.to_string()),
+            ("height", self.y_size.to_string()),
+            ("levels", self.levels.to_string()),
+            ("style", if self.weave { "weave" } else { "standard" }.to_string()),
+            ("topology", self.topology.name().to_string()),
+            ("symmetry", self.symmetry.name().to_string()),
+            ("rooms", self.rooms.to_string()),
+            ("room-labels", if self.room_labels { "numbers" } else { "none" }.to_string()),
+            ("doors", self.doors.to_string()),
+            ("checkpoints", self.checkpoints.to_string()),
+            ("race", self.race_tolerance.map_or("none".to_string(), |tolerance| tolerance.to_string())),
+            ("page-width", self.page_width_in.to_string()),
+            ("page-height", self.page_height_in.to_string()),
+            ("line-width", self.line_width_pt.to_string()),
+            ("layout", self.poster.as_ref().map_or("single".to_string(), |poster| poster.sheets())),
+            ("coloring", self.heat_map.map_or("plain", |heat_map| heat_map.palette.name()).to_string()),
+            ("colored-moves", self.heat_map.and_then(|heat_map| heat_map.neighbourhood).map_or("all".to_string(), |moves| moves.to_string())),
+            ("walls", self.blocks.map_or("lines".to_string(), |ratio| ratio.to_string())),
+            ("wall-color", color_setting(Some(theme.walls))),
+            ("background", color_setting(theme.background)),
+            ("wall-ends", cap_name(theme.caps).to_string()),
+            ("wall-corners", join_name(theme.joins).to_string()),
+            ("wall-line", if theme.dashed { "dashed" } else { "solid" }.to_string()),
+            ("solution-style", theme.solution.name().to_string()),
+            ("caption", if self.title.is_some() { "yes" } else { "no" }.to_string())
+        ]
+    }
+
+    // Read the settings and tuning back from the keywords of a PDF with the
+    // given title.  Returns a description of the first setting that is
+    // missing or cannot be read.
+    pub fn parse(keywords: &str, title: &str) -> Result<(Settings, Tuning), String> {
+        let values = Values(keywords.split(';')
+            .filter_map(|pair| pair.trim().split_once('='))
+            .collect());
+
+        let tuning = Tuning {
+            seed: values.number("seed")?,
+            split_chance: values.number("split-chance")?,
+            corridor_weight: values.number("corridor-weight")?
+        };
+
+        let mut growth = Growth::new();
+        growth.split_chance = tuning.split_chance;
+        growth.max_tips = Some(values.number("max-paths")?).filter(|max_tips| *max_tips > 0);
+        growth.schedule = values.named("path-order", Schedule::named)?;
+
+        let heat_map = match values.get("coloring")? {
+            "plain" => None,
+            _ => Some(HeatMap {
+                palette: values.named("coloring", Palette::named)?,
+                neighbourhood: values.optional("colored-moves", "all")?
+            })
+        };
+
+        let poster = match values.get("layout")? {
+            "single" => None,
+            layout => {
+                let sheets = layout.split_once('x')
+                    .and_then(|(across, down)| Some(Poster::new(across.parse().ok()?, down.parse().ok()?)));
+                Some(sheets.ok_or_else(|| bad("layout"))?)
+            }
+        };
+
+        let theme = Theme {
+            walls: values.named("wall-color", parse_color)?,
+            background: match values.get("background")? {
+                "none" => None,
+                _ => Some(values.named("background", parse_color)?)
+            },
+            caps: values.named("wall-ends", cap_named)?,
+            joins: values.named("wall-corners", join_named)?,
+            dashed: values.flag("wall-line", "dashed", "solid")?,
+            solution: values.named("solution-style", SolutionStyle::named)?
+        };
+
+        let settings = Settings {
+            x_size: values.number("width")?,
+            y_size: values.number("height")?,
+            levels: values.number("levels")?,
+            weave: values.flag("style", "weave", "standard")?,
+            topology: values.named("topology", Topology::named)?,
+            symmetry: values.named("symmetry", Symmetry::named)?,
+            rooms: values.number("rooms")?,
+            room_labels: values.flag("room-labels", "numbers", "none")?,
+            doors: values.number("doors")?,
+            checkpoints: values.number("checkpoints")?,
+            race_tolerance: values.optional("race", "none")?,
+            growth,
+            heat_map,
+            page_width_in: values.number("page-width")?,
+            page_height_in: values.number("page-height")?,
+            line_width_pt: values.number("line-width")?,
+            poster,
+            blocks: values.optional("walls", "lines")?,
+            theme,
+            title: if values.flag("caption", "yes", "no")? { Some(title.to_string()) } else { None }
+        };
+
+        Ok((settings, tuning))
+    }
+}
+
+// The recorded values by name
+struct Values<'a>(HashMap<&'a str, &'a str>);
+
+impl<'a> Values<'a> {
+    fn get(&self, name: &str) -> Result<&'a str, String> {
+        self.0.get(name).copied().ok_or(format!("no {} setting", name))
+    }
+
+    // A value read by the given function, such as one of the named()
+    // functions of the settings' types.
+    fn named<T>(&self, name: &str, read: fn(&str) -> Option<T>) -> Result<T, String> {
+        read(self.get(name)?).ok_or_else(|| bad(name))
+    }
+
+    fn number<T: FromStr>(&self, name: &str) -> Result<T, String> {
+        self.get(name)?.parse().map_err(|_| bad(name))
+    }
+
+    // A number, or None if the value is the given word instead.
+    fn optional<T: FromStr>(&self, name: &str, none: &str) -> Result<Option<T>, String> {
+        match self.get(name)? {
+            value if value == none => Ok(None),
+            _ => self.number(name).map(Some)
+        }
+    }
+
+    // True for the first of two words, and false for the second.
+    fn flag(&self, name: &str, yes: &str, no: &str) -> Result<bool, String> {
+        match self.get(name)? {
+            value if value == yes => Ok(true),
+            value if value == no => Ok(false),
+            _ => Err(bad(name))
+        }
+    }
+}
+
+fn bad(name: &str) -> String {
+    format!("bad {} setting", name)
+}
+
+// A color as recorded in the settings, its red, green and blue separated by
+// commas, or none.
+fn color_setting(color: Option<(f64, f64, f64)>) -> String {
+    match color {
+        Some((r, g, b)) => format!("{},{},{}", r, g, b),
+        None => "none".to_string()
+    }
+}
+
+fn parse_color(text: &str) -> Option<(f64, f64, f64)> {
+    let parts: Vec<f64> = text.split(',').map(|part| part.parse().ok()).collect::<Option<Vec<f64>>>()?;
+    match parts.as_slice() {
+        [r, g, b] => Some((*r, *g, *b)),
+        _ => None
+    }
+}
+
+// Names of the wall ends and corners, as entered at the prompts.  printpdf
+// calls the bevel join Limit.
+fn cap_name(caps: LineCapStyle) -> &'static str {
+    match caps {
+        LineCapStyle::Round => "round",
+        LineCapStyle::Butt => "butt",
+        LineCapStyle::ProjectingSquare => "square"
+    }
+}
+
+fn cap_named(name: &str) -> Option<LineCapStyle> {
+    match name {
+        "square" => Some(LineCapStyle::ProjectingSquare),
+        "round" => Some(LineCapStyle::Round),
+        "butt" => Some(LineCapStyle::Butt),
+        _ => None
+    }
+}
+
+fn join_name(joins: LineJoinStyle) -> &'static str {
+    match joins {
+        LineJoinStyle::Round => "round",
+        LineJoinStyle::Limit => "bevel",
+        LineJoinStyle::Miter => "miter"
+    }
+}
+
+fn join_named(name: &str) -> Option<LineJoinStyle> {
+    match name {
+        "miter" => Some(LineJoinStyle::Miter),
+        "round" => Some(LineJoinStyle::Round),
+        "bevel" => Some(LineJoinStyle::Limit),
+        _ => None
+    }
+}
@@ -0,0 +1,195 @@
+// 3D printer output.  The walls are extruded upward from a base plate and
+// written as a binary STL mesh.  The model is built from the wall blocks
+// stacked on a solid base, and a face is written wherever a solid block meets
+// an empty one.  Every face is one block side, so faces always share whole
+// edges and the mesh is watertight with no gaps for a slicer to repair.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::Maze;
+use crate::walls::WallBlocks;
+
+// Thickness of the plate the walls stand on
+const BASE_THICKNESS_MM: f64 = 2.0;
+
+pub struct StlSettings {
+    pub cell_mm: f64,
+    pub wall_thickness_mm: f64,
+    pub wall_height_mm: f64
+}
+
+type Vertex = (f64, f64, f64);
+
+// Layers of blocks from the bottom up: the base plate, then the walls.
+const LAYERS: i32 = 2;
+
+impl Maze {
+    // Write the mesh.  Returns the number of triangles written.
+    pub fn to_stl(&self, settings: &StlSettings, filename: &str) -> usize {
+        let blocks = self.wall_blocks();
+        let solid = |column: i32, row: i32, layer: i32| match layer {
+            0 => column >= 0 && row >= 0 && column < blocks.columns && row < blocks.rows,
+            1 => blocks.is_solid(column, row),
+            _ => false
+        };
+
+        let x = |column: i32| WallBlocks::edge(column, settings.cell_mm, settings.wall_thickness_mm);
+        let y = |row: i32| WallBlocks::edge(row, settings.cell_mm, settings.wall_thickness_mm);
+        let z = |layer: i32| match layer {
+            0 => 0.0,
+            1 => BASE_THICKNESS_MM,
+            _ => BASE_THICKNESS_MM + settings.wall_height_mm
+        };
+
+        let mut triangles: Vec<[Vertex; 3]> = Vec::new();
+        for layer in 0..LAYERS {
+            for row in 0..blocks.rows {
+                for column in 0..blocks.columns {
+                    if !solid(column, row, layer) {
+                        continue;
+                    }
+
+                    let (x0, x1) = (x(column), x(column + 1));
+                    let (y0, y1) = (y(row), y(row + 1));
+                    let (z0, z1) = (z(layer), z(layer + 1));
+
+                    // Corners of each side are listed counter-clockwise as
+                    // seen from outside the block.
+                    if !solid(column + 1, row, layer) {
+                        add_quad(&mut triangles, [(x1, y0, z0), (x1, y1, z0), (x1, y1, z1), (x1, y0, z1)]);
+                    }
+                    if !solid(column - 1, row, layer) {
+                        add_quad(&mut triangles, [(x0, y0, z0), (x0, y0, z1), (x0, y1, z1), (x0, y1, z0)]);
+                    }
+                    if !solid(column, row + 1, layer) {
+                        add_quad(&mut triangles, [(x0, y1, z0), (x0, y1, z1), (x1, y1, z1), (x1, y1, z0)]);
+                    }
+                    if !solid(column, row - 1, layer) {
+                        add_quad(&mut triangles, [(x0, y0, z0), (x1, y0, z0), (x1, y0, z1), (x0, y0, z1)]);
+                    }
+                    if !solid(column, row, layer + 1) {
+                        add_quad(&mut triangles, [(x0, y0, z1), (x1, y0, z1), (x1, y1, z1), (x0, y1, z1)]);
+                    }
+                    if !solid(column, row, layer - 1) {
+                        add_quad(&mut triangles, [(x0, y0, z0), (x0, y1, z0), (x1, y1, z0), (x1, y0, z0)]);
+                    }
+                }
+            }
+        }
+
+        let mut out = BufWriter::new(File::create(filename).unwrap());
+        write_stl(&mut out, &triangles).unwrap();
+
+        triangles.len()
+    }
+}
+
+// Split a flat quad into two triangles with the same winding.
+fn add_quad(triangles: &mut Vec<[Vertex; 3]>, corners: [Vertex; 4]) {
+    triangles.push([corners[0], corners[1], corners[2]]);
+    triangles.push([corners[0], corners[2], corners[3]]);
+}
+
+// Unit normal of a counter-clockwise triangle.
+fn normal(triangle: &[Vertex; 3]) -> Vertex {
+    let [a, b, c] = *triangle;
+    let (ux, uy, uz) = (b.0 - a.0, b.1 - a.1, b.2 - a.2);
+    let (vx, vy, vz) = (c.0 - a.0, c.1 - a.1, c.2 - a.2);
+    let (nx, ny, nz) = (uy * vz - uz * vy, uz * vx - ux * vz, ux * vy - uy * vx);
+    let length = (nx * nx + ny * ny + nz * nz).sqrt();
+    (nx / length, ny / length, nz / length)
+}
+
+// Binary STL: an 80 byte header, the triangle count, then for each triangle
+// its normal and three corners as little-endian 32-bit floats, followed by
+// two unused bytes.  Units are millimetres.
+fn write_stl<W: Write>(out: &mut W, triangles: &[[Vertex; 3]]) -> std::io::Result<()> {
+    let mut header = [0u8; 80];
+    let title = b"maze-generator";
+    header[..title.len()].copy_from_slice(title);
+    out.write_all(&header)?;
+    out.write_all(&(triangles.len() as u32).to_le_bytes())?;
+
+    for triangle in triangles {
+        let (nx, ny, nz) = normal(triangle);
+        for value in [nx, ny, nz].iter() {
+            out.write_all(&(*value as f32).to_le_bytes())?;
+        }
+        for (x, y, z) in triangle.iter() {
+            for value in [x, y, z].iter() {
+                out.write_all(&(**value as f32).to_le_bytes())?;
+            }
+        }
+        out.write_all(&[0, 0])?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StlSettings, BASE_THICKNESS_MM};
+    use crate::walls::WallBlocks;
+    use crate::Maze;
+    use std::collections::HashMap;
+    use std::{fs, process};
+
+    #[test]
+    fn mesh_is_watertight_and_encloses_the_walls_and_base() {
+        let maze = Maze::carve(3, 2, 1, &[&[(0, 0), (0, 1), (1, 1), (1, 0), (2, 0), (2, 1)]]);
+        let settings = StlSettings { cell_mm: 10.0, wall_thickness_mm: 2.0, wall_height_mm: 5.0 };
+        let path = std::env::temp_dir().join(format!("maze-stl-{}.stl", process::id()));
+        let path = path.to_str().unwrap();
+        let count = maze.to_stl(&settings, path);
+        let stl = fs::read(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(stl.len(), 84 + 50 * count);
+        let float = |offset: usize| {
+            let mut bytes = [0u8; 4];
+            bytes.copy_from_slice(&stl[offset..offset + 4]);
+            f32::from_le_bytes(bytes) as f64
+        };
+        let triangles: Vec<[(f64, f64, f64); 3]> = (0..count)
+            .map(|i| 84 + 50 * i + 12)
+            .map(|at| {
+                let corner = |j: usize| (float(at + 12 * j), float(at + 12 * j + 4), float(at + 12 * j + 8));
+                [corner(0), corner(1), corner(2)]
+            })
+            .collect();
+
+        // Each edge is walked once in each direction by the faces either
+        // side of it, so the mesh has no holes and consistent winding.
+        let key = |v: (f64, f64, f64)| ((v.0 * 1000.0).round() as i64, (v.1 * 1000.0).round() as i64, (v.2 * 1000.0).round() as i64);
+        let mut edges = HashMap::new();
+        for triangle in triangles.iter() {
+            for j in 0..3 {
+                *edges.entry((key(triangle[j]), key(triangle[(j + 1) % 3]))).or_insert(0) += 1;
+            }
+        }
+        for (&(a, b), &times) in edges.iter() {
+            assert_eq!(times, 1);
+            assert_eq!(edges.get(&(b, a)), Some(&1));
+        }
+
+        // The signed volume is positive only if the faces point outward, and
+        // it matches the base plate plus the solid wall blocks.
+        let volume: f64 = triangles.iter().map(|[a, b, c]| {
+            a.0 * (b.1 * c.2 - b.2 * c.1) - a.1 * (b.0 * c.2 - b.2 * c.0) + a.2 * (b.0 * c.1 - b.1 * c.0)
+        }).sum::<f64>() / 6.0;
+
+        let blocks = maze.wall_blocks();
+        let edge = |index: i32| WallBlocks::edge(index, settings.cell_mm, settings.wall_thickness_mm);
+        let mut walls = 0.0;
+        for row in 0..blocks.rows {
+            for column in 0..blocks.columns {
+                if blocks.is_solid(column, row) {
+                    walls += (edge(column + 1) - edge(column)) * (edge(row + 1) - edge(row));
+                }
+            }
+        }
+        let base = edge(blocks.columns) * edge(blocks.rows);
+        assert!((volume - (base * BASE_THICKNESS_MM + walls * settings.wall_height_mm)).abs() < 1e-3);
+    }
+}
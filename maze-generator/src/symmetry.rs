@@ -0,0 +1,278 @@
+// Symmetric mazes.  A horizontal mirror maze reads the same from left to
+// right as from right to left, a vertical mirror maze the same upside down,
+// and a rotational maze the same turned half way around.
+//
+// Only half of the maze is generated: the cells whose image is in the other
+// half are marked as visited beforehand, so no tip enters them.  The passages
+// of the generated half are then copied onto its image, and the two halves
+// are joined by a single passage across the middle that is its own image, so
+// the maze stays a tree.  The copied half is re-rooted at that passage, to
+// keep every edge pointing away from the start.
+//
+// The start area is in the generated half and the finish area in the copy.
+// Under a mirror their images are ordinary cells, so those cells are opened
+// up as rooms of the same size, which keeps the pattern symmetric.  While the
+// half is generated the images carry the area flags, so that reaching the
+// image of the finish area ends the search as reaching the finish would.
+// Once the halves are joined the images become unnumbered rooms, and only
+// the real areas count as the start and the finish.
+
+use rand::prelude::*;
+use std::collections::VecDeque;
+
+use crate::{Cell, Direction, Maze, FINISH_AREA, START_AREA, VISITED};
+
+// The rooms that the images of the start and finish areas become.  They are
+// numbered past any dungeon room, so they are never labelled.
+const START_IMAGE_ROOM: u16 = u16::MAX;
+const FINISH_IMAGE_ROOM: u16 = u16::MAX - 1;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum Symmetry {
+    None,
+    Horizontal,
+    Vertical,
+    Rotational
+}
+
+impl Symmetry {
+    // The symmetry's name, as entered at the prompt.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Symmetry::None => "none",
+            Symmetry::Horizontal => "horizontal",
+            Symmetry::Vertical => "vertical",
+            Symmetry::Rotational => "rotational"
+        }
+    }
+
+    // The symmetry with the given name, if there is one.
+    pub fn named(name: &str) -> Option<Symmetry> {
+        match name {
+            "none" => Some(Symmetry::None),
+            "horizontal" => Some(Symmetry::Horizontal),
+            "vertical" => Some(Symmetry::Vertical),
+            "rotational" => Some(Symmetry::Rotational),
+            _ => None
+        }
+    }
+
+    // The largest maze size, no bigger than the given one, that the symmetry
+    // fits.  The halves must not share a row or column, and a rotational
+    // maze needs a middle column for the passage between them.
+    pub fn fit_size(&self, x_size: u32, y_size: u32) -> (u32, u32) {
+        match self {
+            Symmetry::None => (x_size, y_size),
+            Symmetry::Horizontal => (x_size - x_size % 2, y_size),
+            Symmetry::Vertical => (x_size, y_size - y_size % 2),
+            Symmetry::Rotational => (x_size - (1 - x_size % 2), y_size - y_size % 2)
+        }
+    }
+
+    fn mirror_direction(&self, direction: Direction) -> Direction {
+        match (self, direction) {
+            (Symmetry::Horizontal, Direction::East) | (Symmetry::Horizontal, Direction::West) => direction.opposite(),
+            (Symmetry::Vertical, Direction::North) | (Symmetry::Vertical, Direction::South) => direction.opposite(),
+            (Symmetry::Rotational, _) => direction.opposite(),
+            _ => direction
+        }
+    }
+}
+
+impl Maze {
+    pub fn set_symmetry(&mut self, symmetry: Symmetry) {
+        self.symmetry = symmetry;
+
+        // Give each cell the area of its image as well, which opens up the
+        // rooms that mirror the start and finish areas.
+        if symmetry != Symmetry::None {
+            for index in 0..self.flags.len() {
+                let (x, y) = self.get_image(index);
+                let image = self.index(x, y);
+                self.flags[index] |= self.flags[image] & (START_AREA | FINISH_AREA);
+            }
+        }
+    }
+
+    // The cell that the cell with the given index maps to.
+    fn get_image(&self, index: usize) -> (u32, u32) {
+        let cell = self.get_cell_at(index);
+        match self.symmetry {
+            Symmetry::None => (cell.x, cell.y),
+            Symmetry::Horizontal => (self.x_size - 1 - cell.x, cell.y),
+            Symmetry::Vertical => (cell.x, self.y_size - 1 - cell.y),
+            Symmetry::Rotational => (self.x_size - 1 - cell.x, self.y_size - 1 - cell.y)
+        }
+    }
+
+    // True if the cell is in the half that is copied rather than generated.
+    fn in_image_half(&self, index: usize) -> bool {
+        let (x, y) = self.get_image(index);
+        self.index(x, y) < index
+    }
+
+    // Keep generation to one half of a symmetric maze.
+    pub fn block_image_half(&mut self) {
+        if self.symmetry == Symmetry::None {
+            return;
+        }
+
+        for index in 0..self.flags.len() {
+            if self.in_image_half(index) {
+                self.flags[index] |= VISITED;
+            }
+        }
+    }
+
+    // Copy the generated half of a symmetric maze onto the other half, and
+    // join them.
+    pub fn copy_to_image_half(&mut self) {
+        if self.symmetry == Symmetry::None {
+            return;
+        }
+
+        // The passages of the copy, in both directions, for each cell
+        let mut links: Vec<Vec<Direction>> = vec![Vec::new(); self.flags.len()];
+        for index in 0..self.flags.len() {
+            let cell = self.get_cell_at(index);
+            for direction in Direction::ALL.iter().filter(|direction| cell.has_edge(direction)) {
+                let next = self.get_adjacent(&cell, direction).unwrap();
+                let (x, y) = self.get_image(index);
+                let (next_x, next_y) = self.get_image(self.index(next.x, next.y));
+                let image_direction = self.symmetry.mirror_direction(*direction);
+                links[self.index(x, y)].push(image_direction);
+                links[self.index(next_x, next_y)].push(image_direction.opposite());
+            }
+        }
+
+        // Join the halves across the middle, by a passage that is its own
+        // image.
+        let (join, direction) = self.choose_join();
+        let joined = self.get_adjacent(&join, &direction).unwrap();
+        self.draw_edge(&join, &direction);
+
+        // Draw the copy's edges pointing away from the join.
+        let mut reached: Vec<bool> = vec![false; self.flags.len()];
+        let mut queue: VecDeque<Cell> = VecDeque::new();
+        reached[self.index(joined.x, joined.y)] = true;
+        queue.push_back(joined);
+        while let Some(cell) = queue.pop_front() {
+            for direction in links[self.index(cell.x, cell.y)].clone() {
+                let next = self.get_adjacent(&cell, &direction).unwrap();
+                let next_index = self.index(next.x, next.y);
+                if !reached[next_index] {
+                    reached[next_index] = true;
+                    self.draw_edge(&cell, &direction);
+                    queue.push_back(self.get_cell_at(next_index));
+                }
+            }
+        }
+
+        // The finish is the image of where the generated half reached the
+        // room mirroring the finish area or, when rotating, of the start.
+        let (x, y) = if self.goal_reached {
+            (self.finish_x, self.finish_y)
+        } else {
+            (self.start_x, self.start_y)
+        };
+        let (finish_x, finish_y) = self.get_image(self.index(x, y));
+        self.finish_x = finish_x;
+        self.finish_y = finish_y;
+        self.goal_reached = true;
+
+        self.open_image_areas();
+    }
+
+    // Turn the images of the start and finish areas into rooms, and take
+    // the image's flag off the real areas, which are each other's image when
+    // rotating.
+    fn open_image_areas(&mut self) {
+        let size = self.start_finish_size;
+        for index in 0..self.flags.len() {
+            let cell = self.get_cell_at(index);
+            let start = cell.x < size && cell.y < size;
+            let finish = cell.x >= self.x_size - size && cell.y >= self.y_size - size;
+
+            if cell.start_area() && !start {
+                self.flags[index] &= !START_AREA;
+                if !finish {
+                    self.room_cells[index] = START_IMAGE_ROOM;
+                }
+            }
+            if cell.finish_area() && !finish {
+                self.flags[index] &= !FINISH_AREA;
+                if !start {
+                    self.room_cells[index] = FINISH_IMAGE_ROOM;
+                }
+            }
+        }
+    }
+
+    // A cell in the generated half, and the direction of the passage from it
+    // into the other half.
+    fn choose_join(&mut self) -> (Cell, Direction) {
+        let (x_size, y_size) = (self.x_size, self.y_size);
+        let candidates: Vec<(u32, u32, Direction)> = match self.symmetry {
+            Symmetry::Horizontal => (0..y_size).map(|y| (x_size / 2 - 1, y, Direction::East)).collect(),
+            Symmetry::Vertical => (0..x_size).map(|x| (x, y_size / 2 - 1, Direction::North)).collect(),
+            _ => vec![(x_size / 2, y_size / 2 - 1, Direction::North)]
+        };
+        let candidates: Vec<(u32, u32, Direction)> = candidates.into_iter()
+            .filter(|(x, y, _)| {
+                let cell = self.get_cell(*x, *y);
+                !cell.start_area() && !cell.finish_area()
+            })
+            .collect();
+
+        let (x, y, direction) = *candidates.choose(&mut self.rng).unwrap();
+        (self.get_cell(x, y), direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symmetry;
+    use crate::{Direction, Maze};
+
+    #[test]
+    fn mirrored_half_is_joined_into_one_tree() {
+        // A 4x2 maze mirrored left to right.  The left half is carved by
+        // hand; the cell above the start mirrors the finish, and ends the
+        // solution of the half.
+        let mut maze = Maze::new(4, 2, 1);
+        maze.set_symmetry(Symmetry::Horizontal);
+        maze.block_image_half();
+        maze.mark_as_visited(&maze.get_cell(0, 0));
+        maze.carve_paths(&[&[(0, 0), (0, 1)], &[(0, 0), (1, 0), (1, 1)]]);
+        maze.copy_to_image_half();
+
+        let passage = |x: u32, y: u32, direction: Direction| maze.get_passage(&maze.get_cell(x, y), &direction).is_some();
+        for y in 0..2 {
+            for x in 0..4 {
+                for direction in [Direction::North, Direction::South].iter() {
+                    assert_eq!(passage(x, y, *direction), passage(3 - x, y, *direction));
+                }
+                assert_eq!(passage(x, y, Direction::East), passage(3 - x, y, Direction::West));
+            }
+        }
+
+        // Seven passages join the eight cells, one of them across the middle.
+        let edges: u32 = (0..8).map(|index| (maze.get_cell_at(index).flags & 0x0f).count_ones()).sum();
+        assert_eq!(edges, 7);
+        assert_eq!((0..2).filter(|y| passage(1, *y, Direction::East)).count(), 1);
+
+        let solution = maze.solution_path();
+        assert_eq!(solution.first(), Some(&(0, 0)));
+        assert_eq!(solution.last(), Some(&(3, 1)));
+        assert!(!maze.get_cell(3, 0).start_area() && !maze.get_cell(0, 1).finish_area());
+    }
+
+    #[test]
+    fn size_fits_the_symmetry() {
+        assert_eq!(Symmetry::None.fit_size(5, 5), (5, 5));
+        assert_eq!(Symmetry::Horizontal.fit_size(5, 5), (4, 5));
+        assert_eq!(Symmetry::Vertical.fit_size(5, 5), (5, 4));
+        assert_eq!(Symmetry::Rotational.fit_size(4, 5), (3, 4));
+        assert_eq!(Symmetry::Rotational.fit_size(5, 4), (5, 4));
+    }
+}
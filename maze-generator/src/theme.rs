@@ -0,0 +1,274 @@
+// Themes and solution styles for PDFs.  A theme sets the color of the
+// walls, an optional background fill behind the maze, how the ends and
+// corners of walls are drawn, and whether walls are dashed.  Square ends with
+// mitered corners fill each grid square where walls meet, for a crisp
+// printed look; round ends and corners soften the maze, and bevelled corners
+// clip them.  Dashed walls are for a sketched look, or to trace over by hand.
+//
+// The solution is normally highlighted by filling its cells.  It can instead
+// be drawn as a line through the middle of its cells, a trail of dots, a
+// thin line with an arrow at each turn, or with each cell numbered by the
+// moves from the start.
+
+use printpdf::*;
+
+use crate::topology::DIGIT_WIDTH;
+use crate::{transform_x, transform_y, Document, Maze};
+
+// Color of the solution when drawn as a line, dots, arrows or numbers
+const SOLUTION_COLOR: (f64, f64, f64) = (0.85, 0.1, 0.1);
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum SolutionStyle {
+    Highlight,
+    Line,
+    Dotted,
+    Arrows,
+    Numbers
+}
+
+impl SolutionStyle {
+    // The style's name, as entered at the prompt.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SolutionStyle::Highlight => "highlight",
+            SolutionStyle::Line => "line",
+            SolutionStyle::Dotted => "dotted",
+            SolutionStyle::Arrows => "arrows",
+            SolutionStyle::Numbers => "numbers"
+        }
+    }
+
+    // The style with the given name, if there is one.
+    pub fn named(name: &str) -> Option<SolutionStyle> {
+        match name {
+            "highlight" => Some(SolutionStyle::Highlight),
+            "line" => Some(SolutionStyle::Line),
+            "dotted" => Some(SolutionStyle::Dotted),
+            "arrows" => Some(SolutionStyle::Arrows),
+            "numbers" => Some(SolutionStyle::Numbers),
+            _ => None
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Theme {
+    pub walls: (f64, f64, f64),
+    pub background: Option<(f64, f64, f64)>,
+    pub caps: LineCapStyle,
+    pub joins: LineJoinStyle,
+    pub dashed: bool,
+    pub solution: SolutionStyle
+}
+
+impl Theme {
+    // Black walls on white, with square ends and corners
+    pub fn new() -> Theme {
+        Theme {
+            walls: (0.0, 0.0, 0.0),
+            background: None,
+            caps: LineCapStyle::ProjectingSquare,
+            joins: LineJoinStyle::Miter,
+            dashed: false,
+            solution: SolutionStyle::Highlight
+        }
+    }
+
+    // One of the ready-made themes, or None if there is no such theme.
+    pub fn named(name: &str) -> Option<Theme> {
+        let classic = Theme::new();
+        match name {
+            "classic" => Some(classic),
+            "blueprint" => Some(Theme {
+                walls: (1.0, 1.0, 1.0),
+                background: Some((0.1, 0.25, 0.55)),
+                ..classic
+            }),
+            "parchment" => Some(Theme {
+                walls: (0.35, 0.2, 0.1),
+                background: Some((0.96, 0.91, 0.78)),
+                caps: LineCapStyle::Round,
+                joins: LineJoinStyle::Round,
+                ..classic
+            }),
+            "sketch" => Some(Theme {
+                walls: (0.3, 0.3, 0.3),
+                caps: LineCapStyle::Round,
+                joins: LineJoinStyle::Round,
+                dashed: true,
+                ..classic
+            }),
+            _ => None
+        }
+    }
+
+    pub fn wall_color(&self) -> Color {
+        rgb(self.walls)
+    }
+}
+
+fn rgb((r, g, b): (f64, f64, f64)) -> Color {
+    Color::Rgb(Rgb::new(r, g, b, None))
+}
+
+impl Maze {
+    // Fill the area of the maze with the theme's background, if it has one.
+    pub fn draw_background(&self, layer: &PdfLayerReference, doc: &Document) {
+        let background = match doc.theme.background {
+            Some(background) => background,
+            None => return
+        };
+
+        let offset = Pt(doc.line_width_pt);
+        let (x_size, y_size) = (self.x_size as i32, self.y_size as i32);
+        let x0 = Mm::from(transform_x(0.0, x_size, doc));
+        let y0 = Mm::from(transform_y(0.0, y_size, doc));
+        let x1 = Mm::from(transform_x(self.x_size as f64, x_size, doc) + offset);
+        let y1 = Mm::from(transform_y(self.y_size as f64, y_size, doc) + offset);
+
+        layer.save_graphics_state();
+        layer.set_fill_color(rgb(background));
+        layer.add_shape(Line {
+            points: vec![
+                (Point::new(x0, y0), false),
+                (Point::new(x1, y0), false),
+                (Point::new(x1, y1), false),
+                (Point::new(x0, y1), false)
+            ],
+            is_closed: true,
+            has_fill: true,
+            has_stroke: false,
+            is_clipping_path: false
+        });
+        layer.restore_graphics_state();
+    }
+
+    // Draw the solution in the theme's style, other than highlighting.
+    pub fn draw_styled_solution(&self, layer: &PdfLayerReference, font: &IndirectFontRef, doc: &Document) {
+        self.draw_styled_route(layer, font, doc, &self.solution_route(), rgb(SOLUTION_COLOR));
+    }
+
+    // Draw a route in the theme's style and the given color.
+    pub fn draw_styled_route(&self, layer: &PdfLayerReference, font: &IndirectFontRef, doc: &Document,
+                             route: &[(u32, u32)], color: Color) {
+        let cell_pt = self.cell_pt(doc);
+
+        // The route broken wherever it takes stairs or wraps around
+        let mut runs: Vec<&[(u32, u32)]> = Vec::new();
+        let mut first = 0;
+        for index in 1..=route.len() {
+            if index == route.len() || self.is_jump(route[index - 1], route[index]) {
+                runs.push(&route[first..index]);
+                first = index;
+            }
+        }
+
+        layer.save_graphics_state();
+        layer.set_fill_color(color.clone());
+        layer.set_outline_color(color);
+        layer.set_line_cap_style(LineCapStyle::Round);
+        layer.set_line_join_style(LineJoinStyle::Round);
+
+        match doc.theme.solution {
+            SolutionStyle::Highlight => {},
+            SolutionStyle::Line => {
+                layer.set_outline_thickness(cell_pt * 0.3);
+                for run in runs {
+                    layer.add_shape(self.get_center_line(run, doc));
+                }
+            },
+            SolutionStyle::Dotted => {
+                for (x, y) in route.iter() {
+                    let (center_x, center_y) = self.get_center(*x, *y, doc);
+                    layer.add_shape(Line {
+                        points: utils::calculate_points_for_circle(Pt(cell_pt * 0.15), center_x, center_y),
+                        is_closed: true,
+                        has_fill: true,
+                        has_stroke: false,
+                        is_clipping_path: false
+                    });
+                }
+            },
+            SolutionStyle::Arrows => {
+                layer.set_outline_thickness(cell_pt * 0.08);
+                for run in runs {
+                    layer.add_shape(self.get_center_line(run, doc));
+
+                    // An arrow on the first step, and wherever the route turns
+                    for index in 0..run.len().saturating_sub(1) {
+                        let heading = step(run[index], run[index + 1]);
+                        if index == 0 || heading != step(run[index - 1], run[index]) {
+                            layer.add_shape(self.get_arrow(run[index], heading, doc));
+                        }
+                    }
+                }
+            },
+            SolutionStyle::Numbers => {
+                for (moves, (x, y)) in route.iter().enumerate().skip(1) {
+                    let text = moves.to_string();
+                    let width = text.len() as f64 * DIGIT_WIDTH;
+                    let size = (cell_pt * 0.6).min(cell_pt * 0.9 / width);
+                    let (center_x, center_y) = self.get_center(*x, *y, doc);
+                    layer.use_text(text, size,
+                        Mm::from(center_x - Pt(width * size / 2.0)), Mm::from(center_y - Pt(size * 0.35)), font);
+                }
+            }
+        }
+
+        layer.restore_graphics_state();
+    }
+
+    // The size of a cell on the page, the smaller of its width and height.
+    fn cell_pt(&self, doc: &Document) -> f64 {
+        let (x_size, y_size) = (self.x_size as i32, self.y_size as i32);
+        (transform_x(1.0, x_size, doc).0 - transform_x(0.0, x_size, doc).0)
+            .min(transform_y(1.0, y_size, doc).0 - transform_y(0.0, y_size, doc).0)
+    }
+
+    // The middle of a cell, between its walls.
+    fn get_center(&self, x: u32, y: u32, doc: &Document) -> (Pt, Pt) {
+        let offset = Pt(doc.line_width_pt / 2.0);
+        (transform_x(x as f64 + 0.5, self.x_size as i32, doc) + offset,
+         transform_y(y as f64 + 0.5, self.y_size as i32, doc) + offset)
+    }
+
+    fn get_center_line(&self, run: &[(u32, u32)], doc: &Document) -> Line {
+        Line {
+            points: run.iter()
+                .map(|(x, y)| {
+                    let (center_x, center_y) = self.get_center(*x, *y, doc);
+                    (Point::new(Mm::from(center_x), Mm::from(center_y)), false)
+                })
+                .collect(),
+            is_closed: false,
+            has_fill: false,
+            has_stroke: true,
+            is_clipping_path: false
+        }
+    }
+
+    // An arrowhead in the middle of a cell, pointing along the heading.
+    fn get_arrow(&self, (x, y): (u32, u32), (dx, dy): (f64, f64), doc: &Document) -> Line {
+        let cell_pt = self.cell_pt(doc);
+        let (center_x, center_y) = self.get_center(x, y, doc);
+        let point = |along: f64, across: f64| {
+            let x = center_x.0 + (dx * along - dy * across) * cell_pt;
+            let y = center_y.0 + (dy * along + dx * across) * cell_pt;
+            (Point::new(Mm::from(Pt(x)), Mm::from(Pt(y))), false)
+        };
+
+        Line {
+            points: vec![point(0.35, 0.0), point(-0.15, 0.25), point(-0.15, -0.25)],
+            is_closed: true,
+            has_fill: true,
+            has_stroke: false,
+            is_clipping_path: false
+        }
+    }
+}
+
+// The heading of a step between neighbouring cells
+fn step(from: (u32, u32), to: (u32, u32)) -> (f64, f64) {
+    ((to.0 as i64 - from.0 as i64).signum() as f64, (to.1 as i64 - from.1 as i64).signum() as f64)
+}
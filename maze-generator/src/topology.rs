@@ -0,0 +1,191 @@
+// Wrap-around topologies.  On a cylinder, leaving the maze across its east
+// edge enters it again across the west edge, in the same row.  A torus wraps
+// the north and south edges the same way as well.  A Moebius strip wraps east
+// and west like a cylinder, but with a half twist, so the passage comes back
+// in at the mirrored row: leaving the top row to the east enters the bottom
+// row from the west.
+//
+// The wrap is part of get_adjacent(), so generation, the walls along the
+// edges, and the solution all follow it.  In the PDF, each opening in the
+// outer border is numbered in the margin, with the same number next to the
+// opening it leads to.
+
+use printpdf::*;
+use std::ops::Range;
+
+use crate::{transform_x, transform_y, Cell, Direction, Document, Maze};
+
+// Width of a digit in Helvetica, as a fraction of the font size
+pub const DIGIT_WIDTH: f64 = 0.556;
+
+// Space between the outer wall and a label, in points
+pub const LABEL_GAP_PT: f64 = 2.0;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum Topology {
+    Plane,
+    Cylinder,
+    Torus,
+    Moebius
+}
+
+impl Topology {
+    // The topology's name, as entered at the prompt.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Topology::Plane => "plane",
+            Topology::Cylinder => "cylinder",
+            Topology::Torus => "torus",
+            Topology::Moebius => "moebius"
+        }
+    }
+
+    // The topology with the given name, if there is one.
+    pub fn named(name: &str) -> Option<Topology> {
+        match name {
+            "plane" => Some(Topology::Plane),
+            "cylinder" => Some(Topology::Cylinder),
+            "torus" => Some(Topology::Torus),
+            "moebius" => Some(Topology::Moebius),
+            _ => None
+        }
+    }
+}
+
+impl Maze {
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+
+    // The cell reached by leaving a cell across the edge of its level in the
+    // given direction, if the topology joins that edge to the opposite one.
+    // The start and finish areas are never joined this way, as on a Moebius
+    // strip that would put them next to each other.  Only reached at the
+    // edges, so kept out of the way of get_adjacent()'s usual path.
+    #[cold]
+    pub fn wrap(&self, cell: &Cell, direction: &Direction) -> Option<Cell> {
+        if self.topology == Topology::Plane {
+            return None;
+        }
+
+        let first_row = self.level(cell.y) * self.level_height;
+        let last_row = first_row + self.level_height - 1;
+
+        let (x, y) = match (self.topology, direction) {
+            (Topology::Cylinder, Direction::East) | (Topology::Torus, Direction::East) => (0, cell.y),
+            (Topology::Cylinder, Direction::West) | (Topology::Torus, Direction::West) => (self.x_size - 1, cell.y),
+            (Topology::Moebius, Direction::East) => (0, first_row + last_row - cell.y),
+            (Topology::Moebius, Direction::West) => (self.x_size - 1, first_row + last_row - cell.y),
+            (Topology::Torus, Direction::North) => (cell.x, first_row),
+            (Topology::Torus, Direction::South) => (cell.x, last_row),
+            _ => return None
+        };
+
+        let next = self.get_cell(x, y);
+        if cell.start_area() || cell.finish_area() || next.start_area() || next.finish_area() {
+            None
+        } else {
+            Some(next)
+        }
+    }
+
+    // Number the openings in the outer border of the given rows, in the
+    // margin next to each end of every wrap-around passage.  The rows fill
+    // the printable area of the page.
+    pub fn draw_wrap_labels(&self, layer: &PdfLayerReference, font: &IndirectFontRef, doc: &Document, rows: Range<u32>) {
+        let row_count = (rows.end - rows.start) as i32;
+        let x_size = self.x_size as i32;
+        let page_x = |x: f64| transform_x(x, x_size, doc).0;
+        let page_y = |y: u32| transform_y((y - rows.start) as f64, row_count, doc).0;
+
+        let cell_pt = (page_x(1.0) - page_x(0.0)).min(page_y(rows.start + 1) - page_y(rows.start));
+        let size = (cell_pt * 0.8).min(9.0);
+        let gap = doc.line_width_pt + LABEL_GAP_PT;
+        let width = |label: &str| label.len() as f64 * size * DIGIT_WIDTH;
+        let mut number = 0;
+
+        let label = |text: &str, x: f64, y: f64| {
+            layer.use_text(text, size, Mm::from(Pt(x)), Mm::from(Pt(y)), font);
+        };
+
+        for y in rows.clone() {
+            if self.has_vertical_wall(x_size - 1, y as i32) {
+                continue;
+            }
+
+            let next = self.get_adjacent(&self.get_cell(self.x_size - 1, y), &Direction::East).unwrap();
+            number += 1;
+            let text = number.to_string();
+            label(&text, page_x(x_size as f64) + gap, (page_y(y) + page_y(y + 1)) / 2.0 - size * 0.35);
+            label(&text, page_x(0.0) - LABEL_GAP_PT - width(&text), (page_y(next.y) + page_y(next.y + 1)) / 2.0 - size * 0.35);
+        }
+
+        for x in 0..self.x_size {
+            if self.has_horizontal_wall(x as i32, rows.end as i32 - 1) {
+                continue;
+            }
+
+            let next = self.get_adjacent(&self.get_cell(x, rows.end - 1), &Direction::North).unwrap();
+            number += 1;
+            let text = number.to_string();
+            label(&text, (page_x(x as f64) + page_x(x as f64 + 1.0)) / 2.0 - width(&text) / 2.0, page_y(rows.end) + gap);
+            label(&text, (page_x(next.x as f64) + page_x(next.x as f64 + 1.0)) / 2.0 - width(&text) / 2.0,
+                page_y(rows.start) - LABEL_GAP_PT - size * 0.75);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Topology;
+    use crate::{Direction, Maze};
+
+    // Where leaving a 4x3 maze from the given cell leads, if anywhere.  The
+    // start is at (0, 0) and the finish at (3, 2).
+    fn wrap(topology: Topology, x: u32, y: u32, direction: Direction) -> Option<(u32, u32)> {
+        let mut maze = Maze::new(4, 3, 1);
+        maze.set_topology(topology);
+        maze.wrap(&maze.get_cell(x, y), &direction).map(|cell| (cell.x, cell.y))
+    }
+
+    #[test]
+    fn edges_join_according_to_the_topology() {
+        assert_eq!(wrap(Topology::Plane, 3, 1, Direction::East), None);
+        assert_eq!(wrap(Topology::Cylinder, 3, 1, Direction::East), Some((0, 1)));
+        assert_eq!(wrap(Topology::Cylinder, 0, 1, Direction::West), Some((3, 1)));
+        assert_eq!(wrap(Topology::Cylinder, 1, 2, Direction::North), None);
+        assert_eq!(wrap(Topology::Torus, 1, 2, Direction::North), Some((1, 0)));
+        assert_eq!(wrap(Topology::Torus, 1, 0, Direction::South), Some((1, 2)));
+        assert_eq!(wrap(Topology::Moebius, 0, 2, Direction::West), Some((3, 0)));
+        assert_eq!(wrap(Topology::Moebius, 3, 0, Direction::East), Some((0, 2)));
+        assert_eq!(wrap(Topology::Moebius, 1, 2, Direction::North), None);
+    }
+
+    #[test]
+    fn start_and_finish_areas_are_never_wrapped() {
+        assert_eq!(wrap(Topology::Cylinder, 0, 0, Direction::West), None);
+        assert_eq!(wrap(Topology::Cylinder, 0, 2, Direction::West), None);
+        assert_eq!(wrap(Topology::Torus, 3, 0, Direction::South), None);
+        assert_eq!(wrap(Topology::Moebius, 3, 2, Direction::East), None);
+    }
+
+    #[test]
+    fn solution_follows_a_wrapped_passage() {
+        let mut maze = Maze::new(4, 3, 1);
+        maze.set_topology(Topology::Cylinder);
+        maze.mark_as_visited(&maze.get_cell(0, 0));
+        maze.carve_paths(&[&[(0, 0), (0, 1), (3, 1), (3, 2)]]);
+
+        let passage = maze.get_passage(&maze.get_cell(3, 1), &Direction::East).unwrap();
+        assert_eq!((passage.x, passage.y), (0, 1));
+        assert_eq!(maze.solution_path(), vec![(0, 0), (0, 1), (3, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for topology in [Topology::Plane, Topology::Cylinder, Topology::Torus, Topology::Moebius].iter() {
+            assert!(Topology::named(topology.name()) == Some(*topology));
+        }
+        assert!(Topology::named("sphere").is_none());
+    }
+}
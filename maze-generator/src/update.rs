@@ -0,0 +1,222 @@
+// Incremental updates to saved PDFs.  What printpdf cannot write, or not
+// without holding it all in memory, is appended to the file it saved: new
+// versions of the objects involved and any new objects, with a
+// cross-reference table and trailer that point back to printpdf's.  Only the
+// trailer and the objects involved are read back, not the whole document.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+
+// How many bytes at the end of a saved PDF hold its trailer
+const TAIL_BYTES: u64 = 1024;
+
+pub struct Update {
+    file: File,
+
+    // Length of the saved file, where the update starts
+    length: u64,
+
+    xref: Xref,
+    trailer: Vec<u8>,
+
+    // Number of the next new object
+    size: u64,
+
+    // The objects to write, from "obj" to "endobj", by number
+    objects: Vec<(u64, Vec<u8>)>
+}
+
+impl Update {
+    pub fn open(filename: &str) -> io::Result<Update> {
+        let mut file = OpenOptions::new().read(true).write(true).open(filename)?;
+        let length = file.seek(SeekFrom::End(0))?;
+        let tail = read_at(&mut file, length.saturating_sub(TAIL_BYTES), TAIL_BYTES as usize)?;
+
+        let xref_start = find(&tail, b"startxref")
+            .and_then(|at| number(&tail[at + 9..]))
+            .ok_or_else(|| invalid("no cross-reference table"))?;
+        let trailer = find(&tail, b"trailer")
+            .and_then(|at| dictionary(&tail[at..]))
+            .ok_or_else(|| invalid("no trailer"))?;
+        let size = key_number(&trailer, b"/Size").ok_or_else(|| invalid("no size in the trailer"))?;
+
+        // printpdf writes a single table, with an entry for every object.
+        let xref = Xref { start: xref_start, header: format!("xref\n0 {}\n", size).len() as u64 };
+
+        Ok(Update { file, length, xref, trailer, size, objects: Vec::new() })
+    }
+
+    // The object a key of the trailer refers to, such as /Root.
+    pub fn trailer_reference(&self, key: &str) -> io::Result<u64> {
+        key_number(&self.trailer, key.as_bytes()).ok_or_else(|| invalid(&format!("no {} in the trailer", key)))
+    }
+
+    // An object as saved, from its number to its "endobj".
+    pub fn object(&mut self, id: u64) -> io::Result<Vec<u8>> {
+        self.xref.object(&mut self.file, id)
+    }
+
+    // Write a new version of an object, given as what goes between "obj"
+    // and "endobj".
+    pub fn replace(&mut self, id: u64, body: Vec<u8>) {
+        let mut object = format!("{} 0 obj", id).into_bytes();
+        object.extend_from_slice(&body);
+        object.extend_from_slice(b"\nendobj");
+        self.objects.push((id, object));
+    }
+
+    // Write a new object, and return its number.
+    pub fn add(&mut self, body: Vec<u8>) -> u64 {
+        let id = self.size;
+        self.size += 1;
+        self.replace(id, body);
+        id
+    }
+
+    pub fn write(mut self) -> io::Result<()> {
+        if self.objects.is_empty() {
+            return Ok(());
+        }
+        self.objects.sort_by_key(|&(id, _)| id);
+
+        let mut offset = self.length;
+        let mut entries = String::new();
+        let mut writer = BufWriter::new(&self.file);
+        writer.seek(SeekFrom::End(0))?;
+        for (id, object) in &self.objects {
+            entries += &format!("{} 1\n{:010} 00000 n \n", id, offset + 1);
+            writer.write_all(b"\n")?;
+            writer.write_all(object)?;
+            offset += 1 + object.len() as u64;
+        }
+
+        let trailer = with_value(&self.trailer, b"/Size", &format!(" {}", self.size));
+        writer.write_all(format!("\nxref\n{}trailer\n", entries).as_bytes())?;
+        writer.write_all(&trailer[..trailer.len() - 2])?;
+        writer.write_all(format!("/Prev {}>>\nstartxref\n{}\n%%EOF", self.xref.start, offset + 1).as_bytes())?;
+        writer.flush()
+    }
+}
+
+// Where the objects of a saved PDF are, from its cross-reference table.
+struct Xref {
+    start: u64,
+    header: u64
+}
+
+impl Xref {
+    // An object in the file, from its number to its "endobj".
+    fn object(&self, file: &mut File, id: u64) -> io::Result<Vec<u8>> {
+        let entry = read_at(file, self.start + self.header + id * 20, 20)?;
+        let offset = number(&entry).ok_or_else(|| invalid("a damaged cross-reference table"))?;
+
+        // Only the new chunk, and the end of the one before, can hold the
+        // first "endobj", so that long streams are read in linear time.
+        let mut object = Vec::new();
+        loop {
+            let chunk = read_at(file, offset + object.len() as u64, 65536)?;
+            if chunk.is_empty() {
+                return Err(invalid("an unfinished object"));
+            }
+            let searched = object.len().saturating_sub(5);
+            object.extend_from_slice(&chunk);
+            if let Some(end) = find(&object[searched..], b"endobj") {
+                object.truncate(searched + end + 6);
+                return Ok(object);
+            }
+        }
+    }
+}
+
+// A stream object's body, with its dictionary entries and content.
+pub fn stream_object(entries: &str, content: &[u8]) -> Vec<u8> {
+    let mut body = format!("<<{}/Length {}>>stream\n", entries, content.len()).into_bytes();
+    body.extend_from_slice(content);
+    body.extend_from_slice(b"\nendstream");
+    body
+}
+
+// The content of a stream object, uncompressed.  printpdf compresses some
+// streams, like the XMP metadata, in release builds.
+pub fn stream_content(object: &[u8]) -> io::Result<Vec<u8>> {
+    let start = find(object, b"stream\n").ok_or_else(|| invalid("an object without a stream"))? + 7;
+    let length = key_number(object, b"/Length").ok_or_else(|| invalid("a stream without a length"))?;
+    let content = object.get(start..start + length as usize).ok_or_else(|| invalid("a damaged stream"))?;
+
+    let dictionary = &object[..start];
+    if find(dictionary, b"/FlateDecode").is_none() {
+        return Ok(content.to_vec());
+    }
+
+    let mut dict = lopdf::Dictionary::new();
+    dict.set("Filter", "FlateDecode");
+    lopdf::Stream::new(dict, content.to_vec()).decompressed_content()
+        .map_err(|_| invalid("a stream that could not be uncompressed"))
+}
+
+// The outermost dictionary in the bytes, from its "<<" to its ">>".
+pub fn dictionary(bytes: &[u8]) -> Option<Vec<u8>> {
+    let start = find(bytes, b"<<")?;
+    let end = bytes.windows(2).rposition(|window| window == b">>")?;
+    Some(bytes[start..end + 2].to_vec())
+}
+
+// The dictionary with the value of a key, a number or a reference, replaced.
+pub fn with_value(dictionary: &[u8], key: &[u8], value: &str) -> Vec<u8> {
+    let start = match find(dictionary, key) {
+        Some(at) => at + key.len(),
+        None => return dictionary.to_vec()
+    };
+    let end = dictionary[start..].iter()
+        .position(|&byte| byte == b'/' || byte == b'>')
+        .map_or(dictionary.len(), |length| start + length);
+
+    let mut replaced = dictionary[..start].to_vec();
+    replaced.extend_from_slice(value.as_bytes());
+    replaced.extend_from_slice(&dictionary[end..]);
+    replaced
+}
+
+// The objects an array of references, following a key, refers to.
+pub fn references(dictionary: &[u8], key: &[u8]) -> Vec<u64> {
+    let array = find(dictionary, key)
+        .map(|at| &dictionary[at + key.len()..])
+        .and_then(|rest| Some(&rest[find(rest, b"[")? + 1..find(rest, b"]")?]))
+        .unwrap_or(&[]);
+
+    String::from_utf8_lossy(array).split_whitespace()
+        .step_by(3)
+        .filter_map(|id| id.parse().ok())
+        .collect()
+}
+
+fn read_at(file: &mut File, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(length);
+    file.seek(SeekFrom::Start(offset))?;
+    file.take(length as u64).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub fn find(bytes: &[u8], pattern: &[u8]) -> Option<usize> {
+    bytes.windows(pattern.len()).position(|window| window == pattern)
+}
+
+// The number at the start of the bytes, after any white space.
+fn number(bytes: &[u8]) -> Option<u64> {
+    let digits: String = bytes.iter()
+        .map(|&byte| byte as char)
+        .skip_while(char::is_ascii_whitespace)
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+// The number following a key in a dictionary, or the object number of a
+// reference.
+pub fn key_number(dictionary: &[u8], key: &[u8]) -> Option<u64> {
+    find(dictionary, key).and_then(|at| number(&dictionary[at + key.len()..]))
+}
+
+pub fn invalid(problem: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("the PDF has {}", problem))
+}
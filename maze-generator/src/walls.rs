@@ -0,0 +1,422 @@
+// Wall geometry.  Walls lie on the grid lines between cells, and a wall
+// vertex (x, y) is the lower-left corner of cell (x, y).  Rather than one
+// segment per cell side, walls are merged into polylines that run straight
+// through collinear segments and turn at corners, so that each stroke covers
+// as much of the maze as possible.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::{Cell, Direction, Maze};
+
+// A run of walls through wall vertices.  Only the end points and corners are
+// listed.  A closed polyline returns to its first point.
+pub struct Polyline {
+    pub points: Vec<(i32, i32)>,
+    pub closed: bool
+}
+
+impl Maze {
+    // Check if a horizontal wall is needed between cells (x, y) and (x, y + 1).
+    // Cells outside the maze are allowed, which gives the outer border.  On
+    // the border and between levels, the cells on either side are the ones
+    // get_adjacent() joins, if any, so a wrap-around passage leaves a gap.
+    pub fn has_horizontal_wall(&self, x: i32, y: i32) -> bool {
+        if x == -1 || x == self.x_size as i32 || y == self.y_size as i32 { // left and right margin, and above the top margin
+            return false;
+        }
+
+        let (cell, cell_above) = if y == -1 { // bottom margin
+            let cell_above: Cell = self.get_cell(x as u32, 0);
+            match self.get_adjacent(&cell_above, &Direction::South) {
+                Some(cell) => (cell, cell_above),
+                None => return true
+            }
+        } else {
+            let cell: Cell = self.get_cell(x as u32, y as u32);
+            match self.get_adjacent(&cell, &Direction::North) {
+                Some(cell_above) => (cell, cell_above),
+                None => return true
+            }
+        };
+
+        !((cell_above.has_edge(&Direction::South) || cell.has_edge(&Direction::North)) ||  // There is a path between the two cells
+          (cell.tunnels(&Direction::North) || cell_above.tunnels(&Direction::North)) || // A tunnel passes under one of the cells
+          (cell.start_area() && cell_above.start_area()) || // Two cells within start area
+          (cell.finish_area() && cell_above.finish_area()) || // Two cells within finish area
+          self.same_room(&cell, &cell_above)) // Two cells within a dungeon room
+    }
+
+    // Check if a vertical wall is needed between cells (x, y) and (x + 1, y).
+    pub fn has_vertical_wall(&self, x: i32, y: i32) -> bool {
+        if y == -1 || y == self.y_size as i32 || x == self.x_size as i32 { // top and bottom margin, and past the right margin
+            return false;
+        }
+
+        let (cell, cell_right) = if x == -1 { // left margin
+            let cell_right: Cell = self.get_cell(0, y as u32);
+            match self.get_adjacent(&cell_right, &Direction::West) {
+                Some(cell) => (cell, cell_right),
+                None => return true
+            }
+        } else {
+            let cell: Cell = self.get_cell(x as u32, y as u32);
+            match self.get_adjacent(&cell, &Direction::East) {
+                Some(cell_right) => (cell, cell_right),
+                None => return true
+            }
+        };
+
+        !((cell_right.has_edge(&Direction::West) || cell.has_edge(&Direction::East)) || // There is a path between two cells
+          (cell.tunnels(&Direction::East) || cell_right.tunnels(&Direction::East)) || // A tunnel passes under one of the cells
+          (cell.start_area() && cell_right.start_area()) || // Two cells within start area
+          (cell.finish_area() && cell_right.finish_area()) || // Two cells within finish area
+          self.same_room(&cell, &cell_right)) // Two cells within a dungeon room
+    }
+
+    // Merge the walls of the cells within the given ranges into polylines.
+    // As with draw_maze_region(), the ranges may extend one cell past the
+    // maze on each side to include the outer border.
+    pub fn wall_polylines(&self, x_range: Range<i32>, y_range: Range<i32>) -> Vec<Polyline> {
+        let graph = WallGraph::new(self, x_range, y_range);
+        graph.into_polylines()
+    }
+}
+
+// The walls as a graph on the wall vertices.  Each vertex holds one bit per
+// direction (see Direction::bit()) for the walls that leave it.
+struct WallGraph {
+    x0: i32,
+    y0: i32,
+    width: i32,
+    height: i32,
+    walls: Vec<u8>
+}
+
+impl WallGraph {
+    fn new(maze: &Maze, x_range: Range<i32>, y_range: Range<i32>) -> WallGraph {
+        let mut graph = WallGraph {
+            x0: x_range.start,
+            y0: y_range.start,
+            width: x_range.end - x_range.start + 1,
+            height: y_range.end - y_range.start + 1,
+            walls: Vec::new()
+        };
+        graph.walls = vec![0; (graph.width * graph.height).max(0) as usize];
+
+        for x in x_range {
+            for y in y_range.clone() {
+                if maze.has_horizontal_wall(x, y) {
+                    graph.add_wall(x, y + 1, Direction::East);
+                }
+
+                if maze.has_vertical_wall(x, y) {
+                    graph.add_wall(x + 1, y, Direction::North);
+                }
+            }
+        }
+
+        graph
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        ((y - self.y0) * self.width + (x - self.x0)) as usize
+    }
+
+    fn add_wall(&mut self, x: i32, y: i32, direction: Direction) {
+        let (next_x, next_y) = step(x, y, direction);
+        let start = self.index(x, y);
+        let end = self.index(next_x, next_y);
+        self.walls[start] |= direction.bit();
+        self.walls[end] |= direction.opposite().bit();
+    }
+
+    // Trace polylines until every wall is used.  Polylines first start from
+    // the vertices where walls end or meet at a junction, so they never stop
+    // part way along a corridor.  Whatever is left after that is a closed
+    // loop, which is started from its lowest, left-most vertex (a corner).
+    fn into_polylines(self) -> Vec<Polyline> {
+        let mut remaining = self.walls.clone();
+        let mut polylines: Vec<Polyline> = Vec::new();
+
+        for closed_loops in [false, true].iter() {
+            for y in self.y0..self.y0 + self.height {
+                for x in self.x0..self.x0 + self.width {
+                    let index = self.index(x, y);
+                    if !*closed_loops && self.walls[index].count_ones() == 2 {
+                        continue;
+                    }
+
+                    while remaining[index] != 0 {
+                        polylines.push(self.trace(&mut remaining, x, y));
+                    }
+                }
+            }
+        }
+
+        polylines
+    }
+
+    // Follow unused walls from (x, y), continuing through every vertex where
+    // exactly two walls meet.
+    fn trace(&self, remaining: &mut [u8], x: i32, y: i32) -> Polyline {
+        let mut points = vec![(x, y)];
+        let (mut current_x, mut current_y) = (x, y);
+        let mut previous_direction: Option<Direction> = None;
+
+        loop {
+            let index = self.index(current_x, current_y);
+            let direction = match Direction::ALL.iter().find(|direction| remaining[index] & direction.bit() != 0) {
+                Some(direction) => *direction,
+                None => break
+            };
+
+            let (next_x, next_y) = step(current_x, current_y, direction);
+            remaining[index] &= !direction.bit();
+            remaining[self.index(next_x, next_y)] &= !direction.opposite().bit();
+
+            // Collinear segments extend the last point instead of adding one
+            if previous_direction == Some(direction) {
+                points.pop();
+            }
+            points.push((next_x, next_y));
+            previous_direction = Some(direction);
+
+            current_x = next_x;
+            current_y = next_y;
+            if self.walls[self.index(current_x, current_y)].count_ones() != 2 {
+                break;
+            }
+        }
+
+        let closed = points.len() > 2 && points.first() == points.last();
+        if closed {
+            points.pop();
+        }
+
+        Polyline {
+            points,
+            closed
+        }
+    }
+}
+
+fn step(x: i32, y: i32, direction: Direction) -> (i32, i32) {
+    match direction {
+        Direction::North => (x, y + 1),
+        Direction::East => (x + 1, y),
+        Direction::South => (x, y - 1),
+        Direction::West => (x - 1, y)
+    }
+}
+
+// The walls as solid blocks on a grid that alternates between wall lines and
+// the insides of cells.  Even columns and rows are wall lines and odd ones
+// are cell interiors, so block (2x, 2y) is the post at wall vertex (x, y),
+// block (2x + 1, 2y) is the wall from vertex (x, y) east, and block
+// (2x, 2y + 1) is the wall from vertex (x, y) north.
+pub struct WallBlocks {
+    pub columns: i32,
+    pub rows: i32,
+    solid: Vec<bool>
+}
+
+impl WallBlocks {
+    // Blocks outside the grid are never solid.
+    pub fn is_solid(&self, column: i32, row: i32) -> bool {
+        column >= 0 && row >= 0 && column < self.columns && row < self.rows &&
+            self.solid[(row * self.columns + column) as usize]
+    }
+
+    // Position of the left (or bottom) edge of a block column (or row), for
+    // walls of the given thickness spaced one cell apart.  The grid edge
+    // after the last block is at index columns (or rows).
+    pub fn edge(index: i32, cell: f64, thickness: f64) -> f64 {
+        (index / 2) as f64 * cell + (index % 2) as f64 * thickness
+    }
+}
+
+impl Maze {
+    pub fn wall_blocks(&self) -> WallBlocks {
+        let mut blocks = WallBlocks {
+            columns: 2 * self.x_size as i32 + 1,
+            rows: 2 * self.y_size as i32 + 1,
+            solid: Vec::new()
+        };
+        blocks.solid = vec![false; (blocks.columns * blocks.rows) as usize];
+
+        let mut set = |column: i32, row: i32| {
+            blocks.solid[(row * blocks.columns + column) as usize] = true;
+        };
+
+        // A wall fills its own block and the posts at both ends.
+        for x in -1..self.x_size as i32 + 1 {
+            for y in -1..self.y_size as i32 + 1 {
+                if self.has_horizontal_wall(x, y) {
+                    set(2 * x, 2 * y + 2);
+                    set(2 * x + 1, 2 * y + 2);
+                    set(2 * x + 2, 2 * y + 2);
+                }
+
+                if self.has_vertical_wall(x, y) {
+                    set(2 * x + 2, 2 * y);
+                    set(2 * x + 2, 2 * y + 1);
+                    set(2 * x + 2, 2 * y + 2);
+                }
+            }
+        }
+
+        blocks
+    }
+}
+
+// Trace the boundary between solid and empty blocks.  Each solid block
+// contributes the sides that face an empty block, directed so the block is
+// on the left.  Walls never touch only at a corner, so every boundary vertex
+// has exactly one outgoing side and the sides link up into closed outlines.
+// Points are block grid indices, and only the corners are kept.
+pub fn get_outlines(blocks: &WallBlocks) -> Vec<Vec<(i32, i32)>> {
+    let mut next: HashMap<(i32, i32), (i32, i32)> = HashMap::new();
+
+    for row in 0..blocks.rows {
+        for column in 0..blocks.columns {
+            if !blocks.is_solid(column, row) {
+                continue;
+            }
+
+            if !blocks.is_solid(column, row - 1) {
+                next.insert((column, row), (column + 1, row));
+            }
+            if !blocks.is_solid(column + 1, row) {
+                next.insert((column + 1, row), (column + 1, row + 1));
+            }
+            if !blocks.is_solid(column, row + 1) {
+                next.insert((column + 1, row + 1), (column, row + 1));
+            }
+            if !blocks.is_solid(column - 1, row) {
+                next.insert((column, row + 1), (column, row));
+            }
+        }
+    }
+
+    // Start from the sides in a fixed order, so output is repeatable.
+    let mut starts: Vec<(i32, i32)> = next.keys().copied().collect();
+    starts.sort_unstable();
+
+    let mut outlines: Vec<Vec<(i32, i32)>> = Vec::new();
+    for start in starts {
+        if !next.contains_key(&start) {
+            continue;
+        }
+
+        let mut points: Vec<(i32, i32)> = Vec::new();
+        let mut current = start;
+
+        while let Some(following) = next.remove(&current) {
+            points.push(current);
+            current = following;
+        }
+
+        outlines.push(remove_collinear(points));
+    }
+
+    outlines
+}
+
+// Keep only the points where the outline turns.
+fn remove_collinear(points: Vec<(i32, i32)>) -> Vec<(i32, i32)> {
+    let count = points.len();
+
+    (0..count)
+        .filter(|i| {
+            let previous = points[(i + count - 1) % count];
+            let point = points[*i];
+            let next = points[(i + 1) % count];
+            (point.0 - previous.0) * (next.1 - point.1) != (point.1 - previous.1) * (next.0 - point.0)
+        })
+        .map(|i| points[i])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::difficulty::Tuning;
+    use crate::Maze;
+
+    fn border(maze: &Maze) -> Vec<super::Polyline> {
+        maze.wall_polylines(-1..maze.x_size as i32 + 1, -1..maze.y_size as i32 + 1)
+    }
+
+    #[test]
+    fn straight_walls_merge_into_one_closed_loop() {
+        // A corridor three cells long has only its outer border, which runs
+        // straight between the four corners.
+        let maze = Maze::carve(3, 1, 1, &[&[(0, 0), (1, 0), (2, 0)]]);
+        let polylines = border(&maze);
+
+        assert_eq!(polylines.len(), 1);
+        assert_eq!(polylines[0].points, vec![(0, 0), (0, 1), (3, 1), (3, 0)]);
+        assert!(polylines[0].closed);
+    }
+
+    #[test]
+    fn walls_break_at_ends_and_junctions() {
+        // The wall between (0, 1) and (1, 1) hangs from the top border,
+        // which makes a junction at (1, 2).
+        let maze = Maze::carve(2, 2, 1, &[&[(0, 0), (1, 0), (1, 1)], &[(0, 0), (0, 1)]]);
+        let polylines = border(&maze);
+
+        assert_eq!(polylines.len(), 2);
+        assert_eq!(polylines[0].points, vec![(1, 1), (1, 2)]);
+        assert!(!polylines[0].closed);
+        assert_eq!(polylines[1].points, vec![(1, 2), (2, 2), (2, 0), (0, 0), (0, 2)]);
+    }
+
+    #[test]
+    fn polylines_cover_every_wall_once() {
+        let mut maze = Maze::new(14, 11, 3);
+        maze.set_tuning(&Tuning { seed: 7, ..Tuning::new(0.1) });
+        maze.add_rooms(2, false);
+        maze.generate();
+
+        let mut segments: Vec<((i32, i32), (i32, i32))> = Vec::new();
+        for polyline in border(&maze) {
+            let mut points = polyline.points.clone();
+            if polyline.closed {
+                points.push(points[0]);
+            }
+
+            // Only the ends and the corners are listed.
+            for corner in points.windows(3) {
+                let (a, b, c) = (corner[0], corner[1], corner[2]);
+                assert!((b.0 - a.0) * (c.1 - b.1) != (b.1 - a.1) * (c.0 - b.0), "{:?} is not a corner", b);
+            }
+
+            for run in points.windows(2) {
+                let (dx, dy) = ((run[1].0 - run[0].0).signum(), (run[1].1 - run[0].1).signum());
+                let mut point = run[0];
+                while point != run[1] {
+                    let next = (point.0 + dx, point.1 + dy);
+                    segments.push((point.min(next), point.max(next)));
+                    point = next;
+                }
+            }
+        }
+
+        let mut walls: Vec<((i32, i32), (i32, i32))> = Vec::new();
+        for x in -1..maze.x_size as i32 + 1 {
+            for y in -1..maze.y_size as i32 + 1 {
+                if maze.has_horizontal_wall(x, y) {
+                    walls.push(((x, y + 1), (x + 1, y + 1)));
+                }
+                if maze.has_vertical_wall(x, y) {
+                    walls.push(((x + 1, y), (x + 1, y + 1)));
+                }
+            }
+        }
+
+        segments.sort_unstable();
+        walls.sort_unstable();
+        assert_eq!(segments, walls);
+    }
+}
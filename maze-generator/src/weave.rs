@@ -0,0 +1,173 @@
+// Weave mazes.  A passage may tunnel under a straight corridor that crosses
+// it, coming up in the cell on the far side.  The corridor on top is drawn
+// with its walls running unbroken through the crossing cell, and the walls of
+// the passage underneath stop at them, leaving a gap where it passes under.
+//
+// A tunnel from cell A under cell X to cell B is stored as an ordinary edge
+// from A towards X, along with the direction bits of the tunnel in X's entry
+// of Maze::under.  Since X's own passages all run across the tunnel, an edge
+// along the tunnel can only lead under X (see Maze::get_linked()).
+
+use rand::prelude::*;
+use std::ops::Range;
+
+use crate::{Cell, Direction, Maze};
+
+// How far the walls of the corridor on top are set in from the sides of the
+// crossing cell, as a fraction of the cell size
+const CROSSING_INSET: f64 = 0.25;
+
+// Chance that a tip which is not at a dead end tunnels anyway, when it can
+const TUNNEL_CHANCE: f64 = 0.1;
+
+impl Maze {
+    pub fn set_weave(&mut self, weave: bool) {
+        self.weave = weave;
+    }
+
+    // Advance a tip by tunnelling under a neighbouring corridor, if this is a
+    // weave maze and a tunnel is possible from here.  A tip at a dead end
+    // always tunnels when it can, and any other tip only occasionally.
+    // Returns the cell the tunnel comes up in.
+    pub fn tunnel(&mut self, current: &Cell, dead_end: bool) -> Option<Cell> {
+        if !self.weave || current.under != 0 {
+            return None;
+        }
+
+        if !dead_end && self.rng.gen::<f64>() >= TUNNEL_CHANCE {
+            return None;
+        }
+
+        let options: Vec<Direction> = Direction::ALL.iter()
+            .filter(|direction| self.can_tunnel(current, direction))
+            .copied()
+            .collect();
+        let direction = *options.choose(&mut self.rng)?;
+
+        let crossing = self.get_adjacent(current, &direction).unwrap();
+        let next = self.get_adjacent(&crossing, &direction).unwrap();
+
+        let crossing_index = self.index(crossing.x, crossing.y);
+        self.under[crossing_index] = direction.bit() | direction.opposite().bit();
+        self.draw_edge(current, &direction);
+        self.mark_as_visited(&next);
+
+        Some(self.get_cell(next.x, next.y))
+    }
+
+    // A tunnel can go under the adjacent cell if it is a straight corridor
+    // running across the tunnel, and the cell beyond it is free.
+    fn can_tunnel(&self, current: &Cell, direction: &Direction) -> bool {
+        let crossing = match self.get_adjacent(current, direction) {
+            Some(cell) => cell,
+            None => return false
+        };
+        let next = match self.get_adjacent(&crossing, direction) {
+            Some(cell) => cell,
+            None => return false
+        };
+
+        let across = direction.right();
+        let corridor = across.bit() | across.opposite().bit();
+
+        crossing.visited() && crossing.under == 0 &&
+            !crossing.start_area() && !crossing.finish_area() && !self.has_stairs(&crossing) &&
+            self.connections(&crossing) == corridor &&
+            !next.visited() && !next.start_area() && !next.finish_area()
+    }
+
+    // The direction bits of every passage leaving a cell: its own edges and
+    // the edge it was reached by.
+    fn connections(&self, cell: &Cell) -> u8 {
+        let mut bits = cell.flags & 0x0f;
+        if let Some(direction) = self.get_previous_direction(cell) {
+            bits |= direction.opposite().bit();
+        }
+        bits
+    }
+
+    // The walls of the corridor on top of each crossing within the given
+    // ranges, in cell coordinates.  Each is a bracket that narrows the
+    // corridor as it passes over, leaving the tunnel's walls ending at it.
+    pub fn crossing_walls(&self, x_range: Range<i32>, y_range: Range<i32>) -> Vec<Vec<(f64, f64)>> {
+        let mut walls: Vec<Vec<(f64, f64)>> = Vec::new();
+        if !self.weave {
+            return walls;
+        }
+
+        for y in y_range.start.max(0)..y_range.end.min(self.y_size as i32) {
+            for x in x_range.start.max(0)..x_range.end.min(self.x_size as i32) {
+                let cell = self.get_cell(x as u32, y as u32);
+                let (x0, y0) = (x as f64, y as f64);
+                let (x1, y1) = (x0 + 1.0, y0 + 1.0);
+                let inset = CROSSING_INSET;
+
+                if cell.tunnels(&Direction::East) {
+                    // Corridor on top runs north to south
+                    walls.push(vec![(x0, y1), (x0 + inset, y1), (x0 + inset, y0), (x0, y0)]);
+                    walls.push(vec![(x1, y1), (x1 - inset, y1), (x1 - inset, y0), (x1, y0)]);
+                } else if cell.tunnels(&Direction::North) {
+                    // Corridor on top runs east to west
+                    walls.push(vec![(x0, y0), (x0, y0 + inset), (x1, y0 + inset), (x1, y0)]);
+                    walls.push(vec![(x0, y1), (x0, y1 - inset), (x1, y1 - inset), (x1, y1)]);
+                }
+            }
+        }
+
+        walls
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Direction, Maze};
+
+    // A 3x3 maze with a corridor up the middle column, and a dead end at
+    // (0, 1) beside it.  The only tunnel from there goes east under (1, 1).
+    fn beside_corridor(weave: bool) -> Maze {
+        let mut maze = Maze::carve(3, 3, 1, &[&[(0, 0), (1, 0), (1, 1), (1, 2), (0, 2), (0, 1)]]);
+        maze.set_weave(weave);
+        maze
+    }
+
+    #[test]
+    fn dead_end_tunnels_under_a_crossing_corridor() {
+        let mut maze = beside_corridor(true);
+        let dead_end = maze.get_cell(0, 1);
+        let next = maze.tunnel(&dead_end, true).unwrap();
+        assert_eq!((next.x, next.y), (2, 1));
+
+        // The tunnel is reached from either end but not from on top, and
+        // the corridor on top still runs north to south.
+        let crossing = maze.get_cell(1, 1);
+        assert_eq!(crossing.under, Direction::East.bit() | Direction::West.bit());
+        let passage = |x: u32, y: u32, direction: Direction| {
+            maze.get_passage(&maze.get_cell(x, y), &direction).map(|cell| (cell.x, cell.y))
+        };
+        assert_eq!(passage(0, 1, Direction::East), Some((2, 1)));
+        assert_eq!(passage(2, 1, Direction::West), Some((0, 1)));
+        assert_eq!(passage(1, 1, Direction::East), None);
+        assert_eq!(passage(1, 1, Direction::West), None);
+        assert_eq!(passage(1, 1, Direction::North), Some((1, 2)));
+        assert_eq!(passage(1, 1, Direction::South), Some((1, 0)));
+
+        assert_eq!(maze.crossing_walls(0..3, 0..3), vec![
+            vec![(1.0, 2.0), (1.25, 2.0), (1.25, 1.0), (1.0, 1.0)],
+            vec![(2.0, 2.0), (1.75, 2.0), (1.75, 1.0), (2.0, 1.0)]
+        ]);
+        assert!(maze.crossing_walls(2..3, 0..3).is_empty());
+    }
+
+    #[test]
+    fn no_tunnels_without_weave_or_a_straight_corridor() {
+        let mut maze = beside_corridor(false);
+        let dead_end = maze.get_cell(0, 1);
+        assert!(maze.tunnel(&dead_end, true).is_none());
+
+        // Next to (1, 1) there is a bend, unvisited cells and the border,
+        // but no straight corridor with a free cell beyond it.
+        let mut maze = beside_corridor(true);
+        let below_bend = maze.get_cell(1, 1);
+        assert!(maze.tunnel(&below_bend, true).is_none());
+    }
+}